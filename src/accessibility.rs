@@ -0,0 +1,107 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::{format_minutes, format_range, format_time, turns_label, Task};
+
+// "due in 2 hours", "overdue by 3 days", "no due time"
+pub fn describe_due(time: Option<DateTime<Utc>>, now: DateTime<Utc>) -> String {
+    let Some(time) = time else {
+        return "no due time".to_string();
+    };
+    let delta = time - now;
+    if delta >= TimeDelta::zero() {
+        format!("due in {}", describe_span(delta))
+    } else {
+        format!("overdue by {}", describe_span(-delta))
+    }
+}
+
+pub fn describe_span(delta: TimeDelta) -> String {
+    let (value, unit) = if delta.num_days() >= 1 {
+        (delta.num_days(), "day")
+    } else if delta.num_hours() >= 1 {
+        (delta.num_hours(), "hour")
+    } else {
+        (delta.num_minutes().max(1), "minute")
+    };
+    format!("{} {}{}", value, unit, if value == 1 { "" } else { "s" })
+}
+
+// Строка без цветов и выравнивания, удобная для экранного диктора и синтезатора речи
+pub fn plain_task_line(i: usize, task: &Task, offset_hours: i64, now: DateTime<Utc>) -> String {
+    let status = task.effective_status().label();
+    let mut line = format!("Task {}, {}, {}", i, status, task.description);
+    if task.is_open() || task.time.is_some() {
+        line.push_str(", ");
+        line.push_str(&describe_due(task.deadline(), now));
+    }
+    if task.time.is_some() {
+        line.push_str(&format!(" ({})", format_range(task, offset_hours).trim_matches(['[', ']'])));
+    }
+    if let Some((closed, total)) = task.progress() {
+        line.push_str(&format!(", {} of {} subtasks done", closed, total));
+    }
+    if task.pinned {
+        line.push_str(", pinned");
+    }
+    if task.locked {
+        line.push_str(", locked");
+    }
+    if let Some(priority) = task.priority {
+        line.push_str(&format!(", {} priority", priority.label()));
+    }
+    if !task.tags.is_empty() {
+        line.push_str(&format!(", tagged {}", task.tags.join(", ")));
+    }
+    if let Some(minutes) = task.estimate {
+        line.push_str(&format!(", estimated {}", format_minutes(minutes)));
+    }
+    if let Some(rule) = &task.recurrence {
+        line.push_str(&format!(", repeats every {}{}", rule, turns_label(rule, task.time, offset_hours)));
+    }
+    if task.scheduled.is_some() {
+        line.push_str(&format!(", scheduled for {}", format_time(&task.scheduled, offset_hours).trim_matches(['[', ']'])));
+    }
+    if let Some(deferred) = task.deferred.filter(|_| task.is_deferred(now)) {
+        line.push_str(&format!(", deferred until {}", format_time(&Some(deferred), offset_hours).trim_matches(['[', ']'])));
+    }
+    if let Some(delegate) = &task.delegate {
+        line.push_str(&format!(", delegated to {}", delegate));
+    }
+    if let Some(location) = &task.location {
+        line.push_str(&format!(", at {}", location));
+    }
+    if !task.attachments.is_empty() {
+        line.push_str(&format!(", attached {}", task.attachments.join(", ")));
+    }
+    if !task.intervals.is_empty() {
+        let running = task.intervals.last().is_some_and(|i| i.end.is_none());
+        let spent = crate::format_delta(crate::track::elapsed(&task.intervals, now));
+        line.push_str(&format!(", {} {}", if running { "tracking now, so far" } else { "tracked" }, spent));
+    }
+    if task.pomodoros > 0 {
+        line.push_str(&format!(", {} pomodoros", task.pomodoros));
+    }
+    if let Some(id) = task.short_id() {
+        line.push_str(&format!(", id {}", id));
+    }
+    line
+}
+
+// Пробуем espeak-ng, espeak и say (macOS) по очереди
+pub fn speak(text: &str) -> Result<()> {
+    for program in ["espeak-ng", "espeak", "say"] {
+        let child = Command::new(program).stdin(Stdio::piped()).spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        child.wait()?;
+        return Ok(());
+    }
+    anyhow::bail!("No speech synthesizer found (tried espeak-ng, espeak, say)")
+}
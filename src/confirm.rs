@@ -0,0 +1,65 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Можно ли задавать вопросы: выключается --no-interactive, --porcelain и в batch
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+// [confirm]
+// remove_task = false        # r спрашивает перед удалением
+// remove_session = true      # rs спрашивает, если в сессии есть задачи
+// bulk = 0                   # спрашивать, если команда трогает столько задач и больше (0 - никогда)
+// fuzzy_destructive = false  # спрашивать, если r нашёл задачу нечётким поиском
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    pub remove_task: bool,
+    pub remove_session: bool,
+    pub bulk: usize,
+    pub fuzzy_destructive: bool,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig {
+            remove_task: false,
+            remove_session: true,
+            bulk: 0,
+            fuzzy_destructive: false,
+        }
+    }
+}
+
+impl ConfirmConfig {
+    pub fn is_bulk(&self, count: usize) -> bool {
+        self.bulk > 0 && count >= self.bulk
+    }
+}
+
+pub fn ask(question: &str) -> Result<bool> {
+    println!("{} [y/N]", question);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let input = input.trim().to_lowercase();
+    Ok(input == "y" || input == "yes")
+}
+
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+}
+
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+// Нумерованный список; None, если ничего не выбрано
+pub fn choose(question: &str, options: &[String]) -> Result<Option<usize>> {
+    println!("{}", question);
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+    println!("Pick a number, Enter skips:");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().parse::<usize>().ok().filter(|n| (1..=options.len()).contains(n)).map(|n| n - 1))
+}
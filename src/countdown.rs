@@ -0,0 +1,109 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+
+// Крупный шрифт 5 строк высотой: цифры, ':', 'd' и пробел
+const GLYPH_HEIGHT: usize = 5;
+
+fn glyph(c: char) -> [&'static str; GLYPH_HEIGHT] {
+    match c {
+        '0' => ["█████", "█   █", "█   █", "█   █", "█████"],
+        '1' => ["   █ ", "  ██ ", "   █ ", "   █ ", "  ███"],
+        '2' => ["█████", "    █", "█████", "█    ", "█████"],
+        '3' => ["█████", "    █", " ████", "    █", "█████"],
+        '4' => ["█   █", "█   █", "█████", "    █", "    █"],
+        '5' => ["█████", "█    ", "█████", "    █", "█████"],
+        '6' => ["█████", "█    ", "█████", "█   █", "█████"],
+        '7' => ["█████", "    █", "   █ ", "  █  ", "  █  "],
+        '8' => ["█████", "█   █", "█████", "█   █", "█████"],
+        '9' => ["█████", "█   █", "█████", "    █", "█████"],
+        ':' => ["   ", " █ ", "   ", " █ ", "   "],
+        'd' => ["    █", "    █", "█████", "█   █", "█████"],
+        _ => ["   ", "   ", "   ", "   ", "   "],
+    }
+}
+
+fn render(text: &str) -> Vec<String> {
+    (0..GLYPH_HEIGHT)
+    .map(|row| text.chars().map(|c| glyph(c)[row]).collect::<Vec<_>>().join(" "))
+    .collect()
+}
+
+// "2d 03:14:07", дни только если остались
+fn remaining_text(left: TimeDelta) -> String {
+    let secs = left.num_seconds().max(0);
+    let (days, hours, minutes, seconds) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+    if days > 0 {
+        format!("{}d {:02}:{:02}:{:02}", days, hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    }
+}
+
+// Размер терминала берём из окружения, без лишних зависимостей
+fn terminal_size() -> (usize, usize) {
+    let read = |name: &str, default: usize| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+    (read("COLUMNS", 80), read("LINES", 24))
+}
+
+fn centered(line: &str, width: usize) -> String {
+    let pad = width.saturating_sub(line.chars().count()) / 2;
+    format!("{}{}", " ".repeat(pad), line)
+}
+
+// Перерисовывает экран раз в секунду до наступления срока; Ctrl-C прерывает
+pub fn run(description: &str, due: DateTime<Utc>, flash_under: Option<Duration>, offset_hours: i64) -> Result<()> {
+    let mut stdout = StandardStream::stdout(crate::theme::color_choice());
+    let due_label = (due + TimeDelta::hours(offset_hours)).format("%Y-%m-%d %H:%M").to_string();
+    let mut tick = 0u64;
+
+    loop {
+        let left = due - Utc::now();
+        let finished = left <= TimeDelta::zero();
+        let flashing = finished || flash_under.is_some_and(|f| left.to_std().is_ok_and(|l| l < f));
+        let (width, height) = terminal_size();
+
+        let digits = if finished { render("00:00:00") } else { render(&remaining_text(left)) };
+        let top = height.saturating_sub(GLYPH_HEIGHT + 4) / 2;
+
+        write!(stdout, "\x1b[2J\x1b[H{}", "\n".repeat(top))?;
+        stdout.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(stdout, "{}\n", centered(description, width))?;
+
+        let mut spec = ColorSpec::new();
+        let palette = crate::theme::palette();
+        spec.set_bold(true).set_fg(Some(if flashing { palette.overdue } else { palette.done }));
+        // Мигаем, меняя фон через секунду
+        if flashing && tick.is_multiple_of(2) {
+            spec.set_fg(Some(Color::Black)).set_bg(Some(palette.overdue));
+        }
+        stdout.set_color(&spec)?;
+        for line in &digits {
+            writeln!(stdout, "{}", centered(line, width))?;
+        }
+        stdout.reset()?;
+
+        let footer = if finished { format!("Due now ({})", due_label) } else { format!("until {}", due_label) };
+        writeln!(stdout, "\n{}", centered(&footer, width))?;
+        stdout.flush()?;
+
+        if finished {
+            return Ok(());
+        }
+        tick += 1;
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+// Звонок в терминале и, если есть notify-send, уведомление на рабочем столе
+pub fn ring(title: &str, body: &str) {
+    print!("\x07");
+    let _ = std::io::stdout().flush();
+    let _ = std::process::Command::new("notify-send")
+    .args([title, body])
+    .stderr(std::process::Stdio::null())
+    .status();
+}
@@ -0,0 +1,228 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::mirror::hash;
+use crate::Data;
+
+// Журнал длиннее этого переписываем в tasks.json целиком и начинаем заново
+const COMPACT_AT: u64 = 64 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Хеши того, что сейчас на диске (tasks.json плюс журнал): сессии и остальные поля
+static ON_DISK: Mutex<Option<Hashes>> = Mutex::new(None);
+
+struct Hashes {
+    // Хеш самого tasks.json: журнал начинается с него и без совпадения не применяется
+    base: u64,
+    sessions: HashMap<String, u64>,
+    meta: u64,
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn journal_path(data_path: &Path) -> PathBuf {
+    data_path.with_extension("journal")
+}
+
+// Всё, кроме сессий: текущая сессия, корзина, шаблоны и прочее
+pub fn meta(data: &Data) -> Result<Value> {
+    let mut meta = serde_json::to_value(data)?;
+    if let Value::Object(fields) = &mut meta {
+        fields.remove("sessions");
+    }
+    Ok(meta)
+}
+
+// Обратное к meta: заменяет всё, кроме сессий
+pub fn apply_meta(data: &mut Data, mut meta: Value) -> Result<()> {
+    meta["sessions"] = Value::Object(Default::default());
+    let mut restored: Data = serde_json::from_value(meta)?;
+    restored.sessions = std::mem::take(&mut data.sessions);
+    *data = restored;
+    Ok(())
+}
+
+fn session_texts(data: &Data) -> Result<HashMap<String, String>> {
+    data.sessions.iter().map(|(name, tasks)| Ok((name.clone(), serde_json::to_string(tasks)?))).collect()
+}
+
+// Запоминает состояние на диске после полной записи; base - хеш записанного файла
+pub fn remember(data: &Data, base: u64) -> Result<()> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let sessions = session_texts(data)?.into_iter().map(|(name, text)| (name, hash(&text))).collect();
+    let meta = hash(meta(data)?.to_string());
+    *ON_DISK.lock().unwrap() = Some(Hashes { base, sessions, meta });
+    Ok(())
+}
+
+// То же после загрузки: хеш берём у файла на диске
+pub fn remember_file(data: &Data, data_path: &Path) -> Result<()> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    remember(data, hash(fs::read(data_path).unwrap_or_default()))
+}
+
+fn header(base: u64) -> String {
+    format!("{{\"base\":{}}}", base)
+}
+
+// Первая строка журнала {"base": хеш tasks.json}, дальше {"session": "...", "tasks": [...]}
+// (null - сессию удалили) или {"meta": {...}}. Журнал от другого tasks.json (сбой между
+// полной записью и удалением журнала) устарел и выбрасывается. Недописанная
+// последняя строка после сбоя отбрасывается
+pub fn replay(data: &mut Data, data_path: &Path) -> Result<()> {
+    let journal = journal_path(data_path);
+    let Ok(text) = fs::read_to_string(&journal) else {
+        return Ok(());
+    };
+    let base = hash(fs::read(data_path).unwrap_or_default());
+    let mut lines = text.lines();
+    if lines.next() != Some(header(base).as_str()) {
+        eprintln!("Warning: {} does not belong to the current data file and was dropped", journal.display());
+        fs::remove_file(&journal)?;
+        return Ok(());
+    }
+    for (line_no, line) in lines.enumerate().map(|(i, line)| (i + 1, line)) {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            eprintln!("Warning: {} line {} is incomplete and was ignored", journal.display(), line_no + 1);
+            break;
+        };
+        if let Some(meta) = entry.get("meta") {
+            apply_meta(data, meta.clone())
+            .with_context(|| format!("{} line {} is damaged", journal.display(), line_no + 1))?;
+        } else if let Some(Value::String(name)) = entry.get("session") {
+            match entry.get("tasks") {
+                Some(Value::Null) | None => {
+                    data.sessions.remove(name);
+                }
+                Some(tasks) => {
+                    let tasks = serde_json::from_value(tasks.clone())
+                    .with_context(|| format!("{} line {} is damaged", journal.display(), line_no + 1))?;
+                    data.sessions.insert(name.clone(), tasks);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Дописывает в журнал только изменившиеся сессии. Some(изменилось ли что-то),
+// None - журнал вырос или дельты выключены, пора переписать tasks.json целиком
+pub fn save(data: &Data, data_path: &Path) -> Result<Option<bool>> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+    let guard = ON_DISK.lock().unwrap();
+    let Some(on_disk) = guard.as_ref() else {
+        return Ok(None);
+    };
+
+    let mut lines = Vec::new();
+    let texts = session_texts(data)?;
+    for (name, text) in &texts {
+        if on_disk.sessions.get(name) != Some(&hash(text)) {
+            lines.push(format!("{{\"session\":{},\"tasks\":{}}}", serde_json::to_string(name)?, text));
+        }
+    }
+    for name in on_disk.sessions.keys().filter(|name| !texts.contains_key(*name)) {
+        lines.push(format!("{{\"session\":{},\"tasks\":null}}", serde_json::to_string(name)?));
+    }
+    let meta = meta(data)?.to_string();
+    if hash(&meta) != on_disk.meta {
+        lines.push(format!("{{\"meta\":{}}}", meta));
+    }
+    if lines.is_empty() {
+        return Ok(Some(false));
+    }
+
+    let journal = journal_path(data_path);
+    let size = fs::metadata(&journal).map_or(0, |m| m.len());
+    if size == 0 {
+        lines.insert(0, header(on_disk.base));
+    }
+    let added: usize = lines.iter().map(|l| l.len() + 1).sum();
+    if size + added as u64 > COMPACT_AT {
+        return Ok(None);
+    }
+    let base = on_disk.base;
+    drop(guard);
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&journal)?;
+    file.write_all((lines.join("\n") + "\n").as_bytes())?;
+    file.sync_data()?;
+    remember(data, base)?;
+    Ok(Some(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Task;
+
+    // Свой каталог на тест: тесты идут параллельно
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ttd-delta-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("tasks.json")
+    }
+
+    fn base_file(path: &Path) -> u64 {
+        let mut data = Data::default();
+        data.sessions.insert("home".to_string(), vec![Task { description: "milk".to_string(), ..Default::default() }]);
+        data.sessions.insert("old".to_string(), Vec::new());
+        fs::write(path, serde_json::to_string(&data).unwrap()).unwrap();
+        hash(fs::read(path).unwrap())
+    }
+
+    fn load(path: &Path) -> Data {
+        let mut data: Data = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        replay(&mut data, path).unwrap();
+        data
+    }
+
+    #[test]
+    fn replays_sessions_and_meta_in_order() {
+        let path = scratch("replay");
+        let base = base_file(&path);
+        let journal = [
+            header(base),
+            r#"{"session":"home","tasks":[{"description":"bread","time":null,"status":"todo"}]}"#.to_string(),
+            r#"{"session":"old","tasks":null}"#.to_string(),
+            r#"{"meta":{"current_session":"home","next_id":5}}"#.to_string(),
+            // Недописанная строка после сбоя
+            r#"{"session":"home","tas"#.to_string(),
+        ];
+        fs::write(journal_path(&path), journal.join("\n")).unwrap();
+
+        let data = load(&path);
+        assert_eq!(data.sessions["home"][0].description, "bread");
+        assert!(!data.sessions.contains_key("old"));
+        assert_eq!((data.current_session.as_deref(), data.next_id), (Some("home"), 5));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn journal_of_another_file_is_dropped() {
+        let path = scratch("stale");
+        let base = base_file(&path);
+        let journal = [header(base.wrapping_add(1)), r#"{"session":"home","tasks":[]}"#.to_string()];
+        fs::write(journal_path(&path), journal.join("\n")).unwrap();
+
+        let data = load(&path);
+        assert_eq!(data.sessions["home"][0].description, "milk");
+        assert!(!journal_path(&path).exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+static FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+pub fn set_format(format: ErrorFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn is_json() -> bool {
+    FORMAT.get() == Some(&ErrorFormat::Json)
+}
+
+// Формат ещё не известен, если clap не смог разобрать аргументы, поэтому смотрим сами
+pub fn json_requested_in_args() -> bool {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().any(|a| a == "--error-format=json")
+    || args.windows(2).any(|w| w[0] == "--error-format" && w[1] == "json")
+}
+
+#[derive(Serialize)]
+pub struct NearestMatch {
+    pub description: String,
+    pub confidence: f64,
+}
+
+// Объект ошибки для обёрток и плагинов редакторов, одна строка JSON в stderr
+#[derive(Serialize)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+    pub suggestion: Option<String>,
+    pub nearest_match: Option<NearestMatch>,
+}
+
+impl ErrorReport {
+    pub fn new(code: &'static str, message: impl Into<String>) -> ErrorReport {
+        ErrorReport {
+            code,
+            message: message.into(),
+            suggestion: None,
+            nearest_match: None,
+        }
+    }
+
+    pub fn emit(&self) {
+        eprintln!("{}", serde_json::to_string(self).unwrap_or_default());
+    }
+}
+
+pub fn from_anyhow(error: &anyhow::Error) -> ErrorReport {
+    ErrorReport::new("error", format!("{:#}", error))
+}
+
+pub fn from_clap(error: &clap::Error) -> ErrorReport {
+    let code = match error.kind() {
+        ErrorKind::InvalidSubcommand => "unknown_command",
+        ErrorKind::UnknownArgument => "unknown_argument",
+        ErrorKind::InvalidValue | ErrorKind::ValueValidation => "invalid_value",
+        ErrorKind::MissingRequiredArgument | ErrorKind::MissingSubcommand => "missing_argument",
+        _ => "usage",
+    };
+
+    let rendered = error.render().to_string();
+    // Первый абзац без "error: " и без блока Usage
+    let message = rendered
+    .lines()
+    .take_while(|line| !line.trim().is_empty())
+    .map(str::trim)
+    .collect::<Vec<_>>()
+    .join(" ")
+    .trim_start_matches("error: ")
+    .to_string();
+
+    let suggestion = [ContextKind::SuggestedSubcommand, ContextKind::SuggestedArg, ContextKind::SuggestedValue]
+    .into_iter()
+    .find_map(|kind| match error.get(kind) {
+        Some(ContextValue::String(s)) => Some(s.clone()),
+        Some(ContextValue::Strings(list)) => list.first().cloned(),
+        _ => None,
+    });
+
+    ErrorReport {
+        suggestion,
+        ..ErrorReport::new(code, message)
+    }
+}
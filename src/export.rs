@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::filter::Filter;
+use crate::{format_range, Data, Priority, Status, Task};
+
+#[derive(Clone, Copy, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Ics,
+    Csv,
+    Md,
+    Exec,
+}
+
+#[derive(Serialize)]
+pub struct ExportedTask<'a> {
+    pub session: &'a str,
+    #[serde(flatten)]
+    pub task: &'a Task,
+}
+
+#[derive(Default)]
+pub struct Selection {
+    pub sessions: Vec<String>,
+    pub all_sessions: bool,
+    pub filter: Option<Filter>,
+    pub since: Option<DateTime<Utc>>,
+    // Сессии с sync = false: не выгружаются, даже если их назвали
+    pub local: Vec<String>,
+}
+
+// Общий отбор задач для всех форматов экспорта
+pub fn select<'a>(data: &'a Data, current: &str, selection: &Selection) -> Vec<ExportedTask<'a>> {
+    let now = Utc::now();
+
+    let mut names: Vec<&String> = data.sessions.keys()
+    .filter(|name| !selection.local.contains(name))
+    .filter(|name| {
+        if selection.all_sessions {
+            true
+        } else if selection.sessions.is_empty() {
+            name.as_str() == current
+        } else {
+            selection.sessions.contains(name)
+        }
+    })
+    .collect();
+    names.sort();
+
+    let mut result = Vec::new();
+    for name in names {
+        for task in &data.sessions[name] {
+            if let Some(filter) = &selection.filter {
+                if !filter.matches(task, now) {
+                    continue;
+                }
+            }
+            if let Some(since) = selection.since {
+                if task.time.is_none_or(|t| t < since) {
+                    continue;
+                }
+            }
+            result.push(ExportedTask { session: name, task });
+        }
+    }
+    result
+}
+
+pub fn render(format: Format, tasks: &[ExportedTask], offset_hours: i64) -> Result<String> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(tasks)? + "\n"),
+        Format::Ics => Ok(render_ics(tasks)),
+        Format::Csv => Ok(render_csv(tasks)),
+        Format::Md => Ok(render_md(tasks, offset_hours)),
+        Format::Exec => render(Format::Json, tasks, offset_hours),
+    }
+}
+
+// Передаём канонический JSON во внешнюю команду через stdin.
+// Аргументы идут как есть, без shell: кавычки и | из командной строки не разбираются заново
+pub fn pipe_to_command(command: &[String], json: &str) -> Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        anyhow::bail!("Usage: export exec <command> [args...]");
+    };
+    let command_line = command.join(" ");
+
+    let mut child = Command::new(program)
+    .args(args)
+    .stdin(Stdio::piped())
+    .spawn()
+    .with_context(|| format!("Failed to run '{}'", command_line))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(json.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("Command '{}' exited with {}", command_line, status);
+    }
+    Ok(())
+}
+
+fn render_ics(tasks: &[ExportedTask]) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out = String::new();
+    out.push_str("BEGIN:VCALENDAR\r\n");
+    out.push_str("VERSION:2.0\r\n");
+    out.push_str("PRODID:-//ttd//ttd//EN\r\n");
+
+    for item in tasks {
+        let task = item.task;
+        out.push_str("BEGIN:VTODO\r\n");
+        // UID от id задачи: переименование или перенос в другую сессию не создают дубликат в календаре
+        match task.id {
+            Some(id) => out.push_str(&format!("UID:task-{}@ttd\r\n", id)),
+            None => out.push_str(&format!("UID:{:016x}@ttd\r\n", stable_hash(item.session, &task.description))),
+        }
+        out.push_str(&format!("DTSTAMP:{}\r\n", stamp));
+        out.push_str(&format!("SUMMARY:{}\r\n", escape_ics(&task.description)));
+        let categories: Vec<String> = std::iter::once(item.session).chain(task.tags.iter().map(String::as_str)).map(escape_ics).collect();
+        out.push_str(&format!("CATEGORIES:{}\r\n", categories.join(",")));
+        if let Some(location) = &task.location {
+            out.push_str(&format!("LOCATION:{}\r\n", escape_ics(location)));
+        }
+        // У события начало - DTSTART, а срок - его конец
+        if let Some(start) = task.scheduled.or(task.end.and(task.time)) {
+            out.push_str(&format!("DTSTART:{}\r\n", start.format("%Y%m%dT%H%M%SZ")));
+        }
+        if let Some(due) = task.deadline() {
+            out.push_str(&format!("DUE:{}\r\n", due.format("%Y%m%dT%H%M%SZ")));
+        }
+        if let Some(rrule) = task.recurrence.as_deref().and_then(|r| crate::recurrence::Rule::parse(r).ok()).and_then(|r| r.to_rrule()) {
+            out.push_str(&format!("RRULE:{}\r\n", rrule));
+        }
+        // RFC 5545: 1 - самый высокий, 9 - самый низкий
+        if let Some(priority) = task.priority {
+            let level = match priority {
+                Priority::Critical => 1,
+                Priority::High => 3,
+                Priority::Medium => 5,
+                Priority::Low => 9,
+            };
+            out.push_str(&format!("PRIORITY:{}\r\n", level));
+        }
+        out.push_str(match task.status {
+            Status::Done => "STATUS:COMPLETED\r\n",
+            Status::Cancelled => "STATUS:CANCELLED\r\n",
+            Status::InProgress => "STATUS:IN-PROCESS\r\n",
+            Status::Todo | Status::Waiting => "STATUS:NEEDS-ACTION\r\n",
+        });
+        out.push_str("END:VTODO\r\n");
+    }
+
+    out.push_str("END:VCALENDAR\r\n");
+    out.split_terminator("\r\n").map(fold_ics).collect()
+}
+
+// RFC 5545: строки длиннее 75 байт переносятся, продолжение начинается с пробела.
+// Режем только по границе символа
+fn fold_ics(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for ch in line.chars() {
+        if width + ch.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(ch);
+        width += ch.len_utf8();
+    }
+    out + "\r\n"
+}
+
+fn render_csv(tasks: &[ExportedTask]) -> String {
+    let mut out = String::from("session,description,time,done,location,scheduled,end\n");
+    for item in tasks {
+        let time = item.task.time.map(|t| t.to_rfc3339()).unwrap_or_default();
+        let scheduled = item.task.scheduled.map(|t| t.to_rfc3339()).unwrap_or_default();
+        let end = item.task.end.map(|t| t.to_rfc3339()).unwrap_or_default();
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            escape_csv(item.session),
+            escape_csv(&item.task.description),
+            time,
+            item.task.status == Status::Done,
+            escape_csv(item.task.location.as_deref().unwrap_or_default()),
+            scheduled,
+            end
+        ));
+    }
+    out
+}
+
+fn render_md(tasks: &[ExportedTask], offset_hours: i64) -> String {
+    let mut out = String::new();
+    let mut last_session: Option<&str> = None;
+
+    for item in tasks {
+        if last_session != Some(item.session) {
+            if last_session.is_some() {
+                out.push('\n');
+            }
+            out.push_str(&format!("## {}\n\n", item.session));
+            last_session = Some(item.session);
+        }
+
+        let mark = if item.task.is_open() { " " } else { "x" };
+        out.push_str(&format!("- [{}] {}", mark, item.task.description));
+        if item.task.time.is_some() {
+            out.push_str(&format!(" @due({})", format_range(item.task, offset_hours).trim_matches(['[', ']'])));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn escape_ics(text: &str) -> String {
+    text.replace('\\', "\\\\")
+    .replace(';', "\\;")
+    .replace(',', "\\,")
+    .replace('\n', "\\n")
+}
+
+fn escape_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// FNV-1a: UID задачи без id должен оставаться одинаковым между запусками
+fn stable_hash(session: &str, description: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in session.bytes().chain([0]).chain(description.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        assert_eq!(fold_ics("SUMMARY:milk"), "SUMMARY:milk\r\n");
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets() {
+        let line = format!("SUMMARY:{}", "a".repeat(200));
+        let folded = fold_ics(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line + "\r\n");
+    }
+
+    #[test]
+    fn folding_keeps_characters_whole() {
+        let line = format!("SUMMARY:{}", "я".repeat(100));
+        let folded = fold_ics(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line + "\r\n");
+    }
+}
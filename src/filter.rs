@@ -0,0 +1,159 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use clap::ValueEnum;
+
+use crate::{Priority, Status, Task};
+
+// Выражение фильтра: термы через пробел, все должны совпасть.
+// overdue смотрит на жёсткий срок deadline() (конец события или time), behind - на плановую дату.
+// Префикс '!' инвертирует терм: "undone !notime text:milk"
+#[derive(Debug, Clone)]
+enum Term {
+    Status(Status),
+    Undone,
+    Overdue,
+    Timed,
+    NoTime,
+    Scheduled,
+    Behind,
+    Recurring,
+    Text(String),
+    Context(String),
+    Tag(String),
+    // Этот приоритет или выше
+    Priority(Priority),
+    // Срок сегодня по местному времени; число - смещение пояса в часах
+    DueToday(i64),
+    // Срок не раньше сейчас и не позже, чем через столько
+    DueWithin(TimeDelta),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    terms: Vec<(bool, Term)>,
+}
+
+impl Filter {
+    pub fn parse(expr: &str) -> Result<Filter> {
+        let mut terms = Vec::new();
+
+        for word in expr.split_whitespace() {
+            let (negated, word) = match word.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, word),
+            };
+
+            let term = match word {
+                "done" => Term::Status(Status::Done),
+                "started" | "in_progress" => Term::Status(Status::InProgress),
+                "waiting" => Term::Status(Status::Waiting),
+                "cancelled" => Term::Status(Status::Cancelled),
+                "undone" | "todo" => Term::Undone,
+                "overdue" => Term::Overdue,
+                "timed" => Term::Timed,
+                "notime" => Term::NoTime,
+                "scheduled" => Term::Scheduled,
+                "behind" => Term::Behind,
+                "recurring" => Term::Recurring,
+                _ => {
+                    if let Some(text) = word.strip_prefix("text:") {
+                        Term::Text(text.to_lowercase())
+                    } else if let Some(ctx) = word.strip_prefix('@') {
+                        Term::Context(ctx.to_string())
+                    } else if let Some(tag) = word.strip_prefix(['#', '+']).or_else(|| word.strip_prefix("tag:")) {
+                        Term::Tag(tag.to_string())
+                    } else if let Some(level) = word.strip_prefix("priority:") {
+                        Term::Priority(Priority::from_str(level, true).map_err(|_| {
+                            anyhow::anyhow!("Unknown priority '{}'. Use low, medium, high or critical", level)
+                        })?)
+                    } else {
+                        anyhow::bail!(
+                            "Unknown filter term '{}'. Use done, undone, started, waiting, cancelled, overdue, timed, notime, scheduled, behind, recurring, text:<word>, priority:<level>, #tag, +tag, tag:<tag> or @context",
+                            word
+                        );
+                    }
+                }
+            };
+            terms.push((negated, term));
+        }
+
+        Ok(Filter { terms })
+    }
+
+    pub fn matches(&self, task: &Task, now: DateTime<Utc>) -> bool {
+        self.terms.iter().all(|(negated, term)| {
+            let hit = match term {
+                Term::Status(status) => task.effective_status() == *status,
+                Term::Undone => task.is_open(),
+                Term::Overdue => task.is_open() && task.deadline().is_some_and(|t| t < now),
+                Term::Timed => task.time.is_some(),
+                Term::NoTime => task.time.is_none(),
+                Term::Scheduled => task.scheduled.is_some(),
+                Term::Behind => task.is_open() && task.scheduled.is_some_and(|t| t < now),
+                Term::Recurring => task.recurrence.is_some(),
+                Term::Text(text) => task.description.to_lowercase().contains(text.as_str()),
+                Term::Context(ctx) => task.contexts().any(|c| c.eq_ignore_ascii_case(ctx)),
+                Term::Tag(tag) => task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+                Term::Priority(level) => task.priority.is_some_and(|p| p >= *level),
+                Term::DueToday(offset_hours) => {
+                    let offset = TimeDelta::hours(*offset_hours);
+                    task.deadline().is_some_and(|t| (t + offset).date_naive() == (now + offset).date_naive())
+                }
+                Term::DueWithin(span) => task.deadline().is_some_and(|t| t >= now && t <= now + *span),
+            };
+            hit != *negated
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+// Флаги списков: --done, --overdue, --due-within 2d и т.д. Все должны совпасть,
+// как термы выражения, и дают тот же Filter
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct Flags {
+    #[arg(long)]
+    done: bool,
+    #[arg(long, conflicts_with = "done")]
+    undone: bool,
+    #[arg(long)]
+    overdue: bool,
+    #[arg(long)]
+    due_today: bool,
+    #[arg(long, value_name = "SPAN")]
+    due_within: Option<String>,
+    #[arg(long)]
+    no_time: bool,
+}
+
+impl Flags {
+    pub fn to_filter(&self, offset_hours: i64) -> Result<Filter> {
+        let mut terms = Vec::new();
+        if self.done {
+            terms.push((false, Term::Status(Status::Done)));
+        }
+        if self.undone {
+            terms.push((false, Term::Undone));
+        }
+        if self.overdue {
+            terms.push((false, Term::Overdue));
+        }
+        if self.due_today {
+            terms.push((false, Term::DueToday(offset_hours)));
+        }
+        if let Some(span) = &self.due_within {
+            let span = crate::parse_span(span)?;
+            if span <= TimeDelta::zero() {
+                anyhow::bail!("--due-within needs a positive span, e.g. 2d or 12h");
+            }
+            terms.push((false, Term::DueWithin(span)));
+        }
+        if self.no_time {
+            terms.push((false, Term::NoTime));
+        }
+        Ok(Filter { terms })
+    }
+}
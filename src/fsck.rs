@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::{sort_tasks, Data, Status, Task, MAX_DIFFICULTY};
+
+struct Problem {
+    message: String,
+    fixed: bool,
+}
+
+#[derive(Default)]
+struct Checker {
+    problems: Vec<Problem>,
+    warnings: Vec<String>,
+    repair: bool,
+}
+
+impl Checker {
+    // Записывает проблему; fix вызывается только в режиме --repair
+    fn problem(&mut self, message: String, fix: impl FnOnce()) {
+        if self.repair {
+            fix();
+        }
+        self.problems.push(Problem { message, fixed: self.repair });
+    }
+}
+
+// Работает с файлом напрямую, а не через load_data: испорченный файл
+// не должен молча превратиться в пустые данные
+pub fn run(path: &Path, repair: bool) -> Result<()> {
+    let mut checker = Checker { repair, ..Default::default() };
+
+    let tmp_path = path.with_extension("json.tmp");
+    if tmp_path.exists() {
+        checker.problem(format!("Leftover temporary file {} from an interrupted save", tmp_path.display()), || {});
+        if repair {
+            fs::remove_file(&tmp_path)?;
+        }
+    }
+
+    if !path.exists() || fs::metadata(path)?.len() == 0 {
+        println!("{}: no data yet", path.display());
+        return finish(checker);
+    }
+
+    let text = crate::storage::read_to_string(fs::File::open(path)?)?;
+    let mut root: Value = match serde_json::from_str(&text) {
+        Ok(root) => root,
+        Err(e) => anyhow::bail!(
+            "{} is not valid JSON ({}). Nothing can be repaired automatically; restore it from a backup or fix it by hand",
+            path.display(),
+            e
+        ),
+    };
+
+    // Задачи разбираем по одной, чтобы одна битая дата не потеряла всю сессию
+    let raw_sessions = root
+    .get_mut("sessions")
+    .map(|v| std::mem::replace(v, Value::Object(Default::default())))
+    .unwrap_or_default();
+    let mut data: Data = serde_json::from_value(root)
+    .context("Top-level fields of the data file are damaged; fix them by hand")?;
+
+    let mut rejected = Vec::new();
+    if let Value::Object(sessions) = raw_sessions {
+        for (name, raw_tasks) in sessions {
+            let Value::Array(raw_tasks) = raw_tasks else {
+                checker.problem(format!("Session '{}' is not a list of tasks", name), || {});
+                rejected.push(serde_json::json!({ "session": name, "task": raw_tasks }));
+                continue;
+            };
+            let mut tasks = Vec::new();
+            for (i, raw) in raw_tasks.into_iter().enumerate() {
+                match serde_json::from_value::<Task>(raw.clone()) {
+                    Ok(task) => tasks.push(task),
+                    Err(e) => {
+                        checker.problem(format!("Session '{}', task {}: {}", name, i, e), || {});
+                        rejected.push(serde_json::json!({ "session": name, "task": raw }));
+                    }
+                }
+            }
+            // Сортируем как load_data, чтобы номера в отчёте совпадали с 'ttd l'
+            sort_tasks(&mut tasks);
+            data.sessions.insert(name, tasks);
+        }
+    }
+    crate::delta::replay(&mut data, path)?;
+
+    check_data(&mut data, &mut checker);
+
+    // Журнал истории только растёт: --repair выбрасывает из него лишнее
+    let (dropped, total) = crate::history::compact(&data, repair)?;
+    if dropped > 0 && repair {
+        println!("Compacted history.jsonl: dropped {} of {} events", dropped, total);
+    } else if dropped > 0 {
+        checker.warnings.push(format!("history.jsonl has {} of {} events that 'ttd fsck --repair' would drop", dropped, total));
+    }
+
+    if repair && !checker.problems.is_empty() {
+        data.assign_ids();
+        if !rejected.is_empty() {
+            let rejected_path = path.with_file_name("tasks.rejected.json");
+            fs::write(&rejected_path, serde_json::to_string_pretty(&rejected)?)?;
+            println!("Moved {} unreadable tasks to {}", rejected.len(), rejected_path.display());
+        }
+        crate::save_data(&data)?;
+    }
+
+    finish(checker)
+}
+
+fn check_data(data: &mut Data, checker: &mut Checker) {
+    if let Some(current) = data.current_session.clone().filter(|c| !data.sessions.contains_key(c)) {
+        checker.problem(format!("Current session '{}' does not exist", current), || data.current_session = None);
+    }
+
+    let mut names: Vec<String> = data.sessions.keys().cloned().collect();
+    names.sort();
+
+    // id уникальны по всем сессиям; повторный id сбрасываем, assign_ids выдаст новый
+    let mut seen_ids = std::collections::HashSet::new();
+    for name in &names {
+        for (i, task) in data.sessions.get_mut(name).unwrap().iter_mut().enumerate() {
+            if let Some(id) = task.id.filter(|id| !seen_ids.insert(*id)) {
+                checker.problem(
+                    format!("Session '{}', task {} '{}': id {} is already used", name, i, task.description, id),
+                    || task.id = None,
+                );
+            }
+        }
+    }
+
+    for name in names {
+        let tasks = data.sessions.get_mut(&name).unwrap();
+
+        for (i, task) in tasks.iter_mut().enumerate() {
+            let label = format!("Session '{}', task {} '{}'", name, i, task.description);
+
+            if task.description.trim().is_empty() {
+                checker.warnings.push(format!("Session '{}', task {} has an empty description", name, i));
+            }
+            if task.status != Status::Done && task.completed_at.is_some() {
+                checker.problem(format!("{}: task that is not done has a completion time", label), || task.completed_at = None);
+            }
+            if task.difficulty.is_some_and(|d| d > MAX_DIFFICULTY) {
+                checker.problem(
+                    format!("{}: difficulty above {}", label, MAX_DIFFICULTY),
+                    || task.difficulty = Some(MAX_DIFFICULTY),
+                );
+            }
+            if task.delegate.is_none() && task.followup.is_some() {
+                checker.problem(format!("{}: follow-up without a delegate", label), || task.followup = None);
+            }
+            if task.blocked_by.iter().any(|id| !seen_ids.contains(id)) {
+                checker.problem(
+                    format!("{}: depends on a task that no longer exists", label),
+                    || task.blocked_by.retain(|id| seen_ids.contains(id)),
+                );
+            }
+        }
+
+        for (i, task) in tasks.iter().enumerate() {
+            if let Some(first) = tasks[..i].iter().position(|t| t.is_open() && task.is_open() && t.description == task.description) {
+                checker.warnings.push(format!("Session '{}': tasks {} and {} are duplicates '{}'", name, first, i, task.description));
+            }
+        }
+    }
+}
+
+fn finish(checker: Checker) -> Result<()> {
+    for problem in &checker.problems {
+        println!("{} {}", if problem.fixed { "[fixed]" } else { "[error]" }, problem.message);
+    }
+    for warning in &checker.warnings {
+        println!("[warn]  {}", warning);
+    }
+
+    let unfixed = checker.problems.iter().filter(|p| !p.fixed).count();
+    if checker.problems.is_empty() && checker.warnings.is_empty() {
+        println!("No problems found");
+    } else {
+        println!(
+            "{} problems ({} fixed), {} warnings",
+            checker.problems.len(),
+            checker.problems.len() - unfixed,
+            checker.warnings.len()
+        );
+    }
+    if unfixed > 0 {
+        anyhow::bail!("{} problems left, run 'ttd fsck --repair' to fix them", unfixed);
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc};
+
+use crate::{Task, TimeSettings};
+
+const NAME_WIDTH: usize = 24;
+
+// Полоса задачи в днях (включительно): от плана до срока или плана плюс оценка.
+// Без плана полоса заканчивается сроком; задача только со сроком - веха
+fn span(task: &Task, offset: TimeDelta) -> Option<(NaiveDate, NaiveDate)> {
+    let day = |t: DateTime<Utc>| (t + offset).date_naive();
+    let estimate = task.estimate.map(|m| TimeDelta::minutes(i64::from(m)));
+    let (start, end) = match (task.scheduled, task.deadline()) {
+        (Some(start), Some(due)) if due > start => (start, due),
+        (Some(start), _) => (start, start + estimate.unwrap_or_default()),
+        (None, Some(due)) if task.end.is_some() => (task.time?, due),
+        (None, Some(due)) => (due - estimate.unwrap_or_default(), due),
+        (None, None) => return None,
+    };
+    Some((day(start), day(end)))
+}
+
+// Строки - задачи, колонки - дни начиная с сегодняшнего, выходные и праздники - точки.
+// Только ASCII, чтобы можно было вставить в письмо или тикет
+pub fn render(name: &str, tasks: &[Task], time: &TimeSettings, days: u32) -> String {
+    let offset = TimeDelta::hours(time.offset_hours);
+    let today = (Utc::now() + offset).date_naive();
+    let last = today + TimeDelta::days(i64::from(days) - 1);
+    let columns: Vec<NaiveDate> = today.iter_days().take(days as usize).collect();
+
+    let mut out = format!("{}: {} to {}\n\n", name, today.format("%Y-%m-%d"), last.format("%Y-%m-%d"));
+
+    // Над колонками: дата начала каждой недели (week_starts) и буквы дней недели
+    let mut dates = vec![' '; columns.len()];
+    for (i, date) in columns.iter().enumerate().filter(|(_, d)| d.weekday() == time.week_start) {
+        let label = date.format("%m-%d").to_string();
+        if let Some(slots) = dates.get_mut(i..i + label.len()) {
+            for (slot, c) in slots.iter_mut().zip(label.chars()) {
+                *slot = c;
+            }
+        }
+    }
+    let weekdays: String = columns.iter().map(|d| "MTWTFSS".as_bytes()[d.weekday().num_days_from_monday() as usize] as char).collect();
+    let dates: String = dates.iter().collect();
+    if !dates.trim().is_empty() {
+        out.push_str(&format!("{:NAME_WIDTH$} {}\n", "", dates.trim_end()));
+    }
+    out.push_str(&format!("{:NAME_WIDTH$} {}\n", "", weekdays));
+
+    let mut rows = 0;
+    for task in tasks {
+        let Some((start, end)) = span(task, offset) else {
+            continue;
+        };
+        if start > last {
+            continue;
+        }
+        rows += 1;
+        let bar: String = columns.iter().map(|&day| {
+            let day_off = !time.holidays.is_working_day(day);
+            if start == end && day == start {
+                '*'
+            } else if day >= start && day <= end {
+                if task.is_open() { '#' } else { '=' }
+            } else if day_off {
+                '.'
+            } else {
+                ' '
+            }
+        })
+        .collect();
+        // Начало или вся задача раньше сегодняшнего дня
+        let marker = if end < today && task.is_open() { '!' } else if start < today { '<' } else { '|' };
+        let label: String = task.description.chars().take(NAME_WIDTH).collect();
+        out.push_str(&format!("{:NAME_WIDTH$}{}{}\n", label, marker, bar.trim_end()));
+    }
+
+    if rows == 0 {
+        out.push_str("\nNo scheduled or timed tasks in this range\n");
+    } else {
+        out.push_str("\n# planned  = done  * due  < started earlier  ! overdue\n");
+    }
+    out
+}
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::Task;
+
+const BLOCKING_TAG: &str = "blocking";
+const HOOK_MARKER: &str = "# installed by ttd git-hook install";
+
+// Путь к хуку спрашиваем у git: так работают и worktree, и core.hooksPath
+fn hook_path() -> Result<PathBuf> {
+    let output = Command::new("git")
+    .args(["rev-parse", "--git-path", "hooks/pre-push"])
+    .output()
+    .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!("Not inside a git repository");
+    }
+    Ok(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+pub fn install(force: bool) -> Result<()> {
+    let path = hook_path()?;
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            anyhow::bail!("{} already exists and was not installed by ttd, use --force to replace it", path.display());
+        }
+    }
+
+    let script = format!(
+        "#!/bin/sh\n{}\ncommand -v ttd >/dev/null 2>&1 || exit 0\nexec ttd git-hook check\n",
+        HOOK_MARKER
+    );
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, script)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    println!("Installed pre-push hook at {}", path.display());
+    println!("Open tasks tagged #{} will be reported on push", BLOCKING_TAG);
+    Ok(())
+}
+
+// Предупреждает об открытых #blocking задачах; с block = true не даёт сделать push
+pub fn check(session: &str, tasks: &[Task], block: bool) -> Result<()> {
+    let blocking: Vec<(usize, &Task)> = tasks
+    .iter()
+    .enumerate()
+    .filter(|(_, t)| t.is_open() && t.tags.iter().any(|tag| tag.eq_ignore_ascii_case(BLOCKING_TAG)))
+    .collect();
+
+    if blocking.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("ttd: {} open #{} tasks in session '{}':", blocking.len(), BLOCKING_TAG, session);
+    for (i, task) in &blocking {
+        eprintln!("  #{} {}", i, task.description);
+    }
+    if block {
+        anyhow::bail!("Push blocked by ttd, finish these tasks or push with --no-verify");
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use xdg::BaseDirectories;
+
+use crate::{format_time, Data, Status};
+
+// Что нужно от задачи, чтобы заметить изменение: сессия, статус, время, описание
+struct Seen {
+    session: String,
+    status: Status,
+    time: Option<DateTime<Utc>>,
+    description: String,
+}
+
+pub struct Snapshot {
+    tasks: HashMap<u64, Seen>,
+    trashed: HashSet<u64>,
+}
+
+impl Snapshot {
+    pub fn take(data: &Data) -> Snapshot {
+        let mut tasks = HashMap::new();
+        for (session, list) in &data.sessions {
+            for task in list {
+                if let Some(id) = task.id {
+                    tasks.insert(id, Seen {
+                        session: session.clone(),
+                        status: task.status,
+                        time: task.time,
+                        description: task.description.clone(),
+                    });
+                }
+            }
+        }
+        let trashed = data.trash.iter().filter_map(|e| e.task.id).collect();
+        Snapshot { tasks, trashed }
+    }
+}
+
+// Одна строка history.jsonl рядом с tasks.json
+#[derive(Serialize, Deserialize)]
+struct Event {
+    id: u64,
+    at: DateTime<Utc>,
+    what: String,
+}
+
+fn log_path() -> Result<PathBuf> {
+    let xdg = BaseDirectories::with_prefix("ttd")?;
+    Ok(xdg.place_config_file("history.jsonl")?)
+}
+
+fn status_event(status: Status) -> String {
+    match status {
+        Status::Todo => "reopened".to_string(),
+        other => format!("marked {}", other.label()),
+    }
+}
+
+fn time_label(time: Option<DateTime<Utc>>, offset_hours: i64) -> String {
+    format_time(&time, offset_hours).trim_matches(['[', ']']).trim().to_string()
+}
+
+// Сравнивает состояние до и после запуска и дописывает события в журнал
+pub fn record(before: &Snapshot, data: &Data, offset_hours: i64) -> Result<()> {
+    let after = Snapshot::take(data);
+    let at = Utc::now();
+    let mut events = Vec::new();
+
+    for (&id, new) in &after.tasks {
+        let Some(old) = before.tasks.get(&id) else {
+            let verb = if before.trashed.contains(&id) { "restored to" } else { "created in" };
+            events.push(Event { id, at, what: format!("{} '{}'", verb, new.session) });
+            continue;
+        };
+        if old.session != new.session {
+            events.push(Event { id, at, what: format!("moved from '{}' to '{}'", old.session, new.session) });
+        }
+        if old.description != new.description {
+            events.push(Event { id, at, what: format!("renamed from '{}'", old.description) });
+        }
+        if old.status != new.status {
+            events.push(Event { id, at, what: status_event(new.status) });
+        }
+        if old.time != new.time {
+            let what = format!("time {} -> {}", time_label(old.time, offset_hours), time_label(new.time, offset_hours));
+            events.push(Event { id, at, what });
+        }
+    }
+    for (&id, old) in &before.tasks {
+        if !after.tasks.contains_key(&id) {
+            let place = if after.trashed.contains(&id) { " to trash" } else { "" };
+            events.push(Event { id, at, what: format!("removed from '{}'{}", old.session, place) });
+        }
+    }
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(log_path()?)?;
+    for event in events {
+        writeln!(file, "{}", serde_json::to_string(&event)?)?;
+    }
+    Ok(())
+}
+
+// Сколько последних событий хранить на задачу: у повторяющейся они копятся без конца
+const KEEP_PER_TASK: usize = 200;
+
+// Для fsck: выбрасывает нечитаемые строки и события задач, которых нет ни в данных,
+// ни в корзине, а у каждой задачи оставляет последние KEEP_PER_TASK.
+// Возвращает (выброшено, было); файл переписывается только при write
+pub fn compact(data: &Data, write: bool) -> Result<(usize, usize)> {
+    let path = log_path()?;
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok((0, 0));
+    };
+    let known: HashSet<u64> = data.sessions.values()
+    .flatten()
+    .chain(data.trash.iter().map(|e| &e.task))
+    .filter_map(|t| t.id)
+    .collect();
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    let mut kept: Vec<Event> = lines.iter()
+    .rev()
+    .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+    .filter(|e| known.contains(&e.id))
+    .filter(|e| {
+        let count = counts.entry(e.id).or_default();
+        *count += 1;
+        *count <= KEEP_PER_TASK
+    })
+    .collect();
+    kept.reverse();
+
+    let dropped = lines.len() - kept.len();
+    if write && dropped > 0 {
+        let mut out = String::new();
+        for event in &kept {
+            out.push_str(&serde_json::to_string(event)?);
+            out.push('\n');
+        }
+        let tmp = path.with_extension("jsonl.tmp");
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, &path)?;
+    }
+    Ok((dropped, lines.len()))
+}
+
+pub fn print(id: u64, offset_hours: i64) -> Result<()> {
+    let text = fs::read_to_string(log_path()?).unwrap_or_default();
+    let events: Vec<Event> = text.lines()
+    .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+    .filter(|e| e.id == id)
+    .collect();
+    if events.is_empty() {
+        println!("  no recorded changes");
+    }
+    for event in events {
+        println!("  {} {}", format_time(&Some(event.at), offset_hours), event.what);
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc, Weekday};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+// [holidays]
+// file = "~/.config/ttd/holidays.txt"
+// dates = { "12-25" = "Christmas", "2026-04-06" = "Easter Monday" }
+#[derive(Deserialize, Default)]
+pub struct HolidayConfig {
+    file: Option<String>,
+    #[serde(default)]
+    dates: HashMap<String, String>,
+}
+
+// Нерабочие дни: точные даты и ежегодные ("12-25")
+#[derive(Default)]
+pub struct Holidays {
+    dates: HashMap<NaiveDate, String>,
+    yearly: HashMap<(u32, u32), String>,
+}
+
+impl Holidays {
+    pub fn load(config: &HolidayConfig) -> Result<Holidays> {
+        let mut holidays = Holidays::default();
+        if let Some(file) = &config.file {
+            let path = crate::expand_home(file);
+            let text = fs::read_to_string(&path).with_context(|| format!("Failed to read holidays file {}", path.display()))?;
+            // Строка файла: "2026-12-25 Christmas" или "12-25 Christmas", # - комментарий
+            for (line_no, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (date, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                holidays.add(date, name.trim()).with_context(|| format!("{}:{}", path.display(), line_no + 1))?;
+            }
+        }
+        for (date, name) in &config.dates {
+            holidays.add(date, name).context("Invalid date in [holidays]")?;
+        }
+        Ok(holidays)
+    }
+
+    fn add(&mut self, date: &str, name: &str) -> Result<()> {
+        let name = if name.is_empty() { "holiday" } else { name }.to_string();
+        if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            self.dates.insert(date, name);
+            return Ok(());
+        }
+        // Год-заглушка високосный, чтобы "02-29" тоже проходило проверку
+        match NaiveDate::parse_from_str(&format!("2000-{}", date), "%Y-%m-%d") {
+            Ok(day) => {
+                self.yearly.insert((day.month(), day.day()), name);
+                Ok(())
+            }
+            Err(_) => anyhow::bail!("Expected YYYY-MM-DD or MM-DD, got '{}'", date),
+        }
+    }
+
+    pub fn name(&self, date: NaiveDate) -> Option<&str> {
+        self.dates.get(&date).or_else(|| self.yearly.get(&(date.month(), date.day()))).map(String::as_str)
+    }
+
+    pub fn name_at(&self, time: DateTime<Utc>, offset_hours: i64) -> Option<&str> {
+        self.name((time + TimeDelta::hours(offset_hours)).date_naive())
+    }
+
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && self.name(date).is_none()
+    }
+}
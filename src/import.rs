@@ -0,0 +1,453 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{parse_absolute_time, parse_relative_time, sort_tasks, Priority, Status, Task, TimeSettings};
+
+#[derive(Default)]
+pub struct ImportReport {
+    pub added: usize,
+    pub merged: usize,
+    pub skipped: usize,
+    pub failed: Vec<(String, String)>,
+    // Что стало с уже известными задачами, по строке на каждую; видно в --dry-run
+    pub duplicates: Vec<String>,
+}
+
+impl ImportReport {
+    pub fn print(&self, source: &str, dry_run: bool) {
+        println!(
+            "{}{}: {} added, {} merged, {} skipped as duplicates, {} failed",
+            if dry_run { "[dry run] " } else { "" },
+            source,
+            self.added,
+            self.merged,
+            self.skipped,
+            self.failed.len()
+        );
+        for (item, reason) in &self.failed {
+            println!("  {}: {}", item, reason);
+        }
+        if dry_run {
+            for line in &self.duplicates {
+                println!("  {}", line);
+            }
+            println!("Nothing was saved");
+        }
+    }
+}
+
+// Дубликат - то же описание без учёта регистра, как и при проверке в a
+fn find_same<'a>(tasks: &'a mut [Task], description: &str) -> Option<&'a mut Task> {
+    let description = description.to_lowercase();
+    tasks.iter_mut().find(|t| t.description.to_lowercase() == description)
+}
+
+fn existing_label(existing: &Task, imported: &str) -> String {
+    if existing.description == imported {
+        format!("'{}'", imported)
+    } else {
+        format!("'{}' (as '{}')", imported, existing.description)
+    }
+}
+
+// Добавляет задачу или объединяет с существующей с тем же описанием
+pub fn merge_task(tasks: &mut Vec<Task>, task: Task, report: &mut ImportReport) {
+    match find_same(tasks, &task.description) {
+        Some(existing) if existing.time.is_none() && task.time.is_some() => {
+            existing.time = task.time;
+            report.merged += 1;
+            report.duplicates.push(format!("merged {}: time added", existing_label(existing, &task.description)));
+        }
+        Some(existing) => {
+            report.skipped += 1;
+            report.duplicates.push(format!("skipped {}: already there", existing_label(existing, &task.description)));
+        }
+        None => {
+            tasks.push(task);
+            report.added += 1;
+        }
+    }
+}
+
+pub fn import_checklist(content: &str, tasks: &mut Vec<Task>, time: &TimeSettings) -> ImportReport {
+    let mut report = ImportReport::default();
+
+    for (line_no, line) in content.lines().enumerate() {
+        let Some((done, text)) = parse_checklist_line(line) else {
+            continue;
+        };
+
+        let (description, due) = match split_due(text) {
+            Ok(parts) => parts,
+            Err(e) => {
+                report.failed.push((format!("line {}", line_no + 1), e.to_string()));
+                continue;
+            }
+        };
+
+        let time = match due.map(|d| parse_due(d, time)).transpose() {
+            Ok(time) => time,
+            Err(e) => {
+                report.failed.push((format!("line {}", line_no + 1), e.to_string()));
+                continue;
+            }
+        };
+
+        if description.is_empty() {
+            report.failed.push((format!("line {}", line_no + 1), "empty task description".to_string()));
+            continue;
+        }
+
+        let task = Task {
+            description,
+            time,
+            status: if done { Status::Done } else { Status::Todo },
+            created_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        merge_task(tasks, task, &mut report);
+    }
+
+    sort_tasks(tasks);
+    report
+}
+
+// "- [ ] text", "* [x] text", "+ [X] text"
+fn parse_checklist_line(line: &str) -> Option<(bool, &str)> {
+    let rest = line.trim_start().strip_prefix(['-', '*', '+'])?;
+    let rest = rest.strip_prefix(' ')?.trim_start();
+    let (done, rest) = if let Some(r) = rest.strip_prefix("[ ]") {
+        (false, r)
+    } else if let Some(r) = rest.strip_prefix("[x]").or_else(|| rest.strip_prefix("[X]")) {
+        (true, r)
+    } else {
+        return None;
+    };
+    Some((done, rest.trim()))
+}
+
+fn split_due(text: &str) -> Result<(String, Option<&str>)> {
+    let Some(start) = text.find("@due(") else {
+        return Ok((text.to_string(), None));
+    };
+    let inner_start = start + "@due(".len();
+    let Some(len) = text[inner_start..].find(')') else {
+        anyhow::bail!("unclosed @due(...) annotation");
+    };
+    let due = text[inner_start..inner_start + len].trim();
+    let description = format!("{} {}", text[..start].trim(), text[inner_start + len + 1..].trim());
+    Ok((description.trim().to_string(), Some(due)))
+}
+
+// Принимает "2025-08-01 14:00", "2025-08-01", "in 2h", "at 14h30m"
+fn parse_due(due: &str, time: &TimeSettings) -> Result<DateTime<Utc>> {
+    if let Some(rel) = due.strip_prefix("in ") {
+        return parse_relative_time(rel.trim(), time);
+    }
+    if let Some(abs) = due.strip_prefix("at ") {
+        return parse_absolute_time(abs.trim(), time);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(due, "%Y-%m-%d %H:%M")
+    .ok()
+    .or_else(|| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)));
+
+    match naive {
+        Some(local) => Ok(DateTime::from_naive_utc_and_offset(local - TimeDelta::hours(time.offset_hours), Utc)),
+        None => parse_absolute_time(due, time)
+        .map_err(|e| anyhow::anyhow!("bad @due({}): {}", due, e)),
+    }
+}
+
+// Поля задачи, в которые можно направить поле чужого JSON
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Description,
+    Time,
+    Session,
+    Done,
+    Status,
+    Details,
+    Tags,
+    Priority,
+    Location,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Field> {
+        Ok(match name {
+            "description" => Field::Description,
+            "time" => Field::Time,
+            "session" => Field::Session,
+            "done" => Field::Done,
+            "status" => Field::Status,
+            "details" => Field::Details,
+            "tags" => Field::Tags,
+            "priority" => Field::Priority,
+            "location" => Field::Location,
+            _ => anyhow::bail!(
+                "Unknown task field '{}'. Use description, time, session, done, status, details, tags, priority or location",
+                name
+            ),
+        })
+    }
+}
+
+// Экспорт Напоминаний (Apple Reminders) через Shortcuts или reminders-cli
+const REMINDERS_MAP: &str = "title=description,dueDate=time,list=session,isCompleted=done,notes=details,priority=priority";
+
+// "title=description,due.date=time": слева путь в объекте через точку, справа поле задачи.
+// Вместо списка можно дать имя карты из [import_maps] в конфиге или встроенной reminders
+pub fn parse_map(spec: &str, saved: &BTreeMap<String, String>) -> Result<Vec<(String, Field)>> {
+    let spec = match saved.get(spec).map(String::as_str) {
+        Some(saved) => saved,
+        None if spec == "reminders" => REMINDERS_MAP,
+        None => spec,
+    };
+    let mut map = Vec::new();
+    for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((path, field)) = pair.split_once('=') else {
+            anyhow::bail!("Bad mapping '{}', expected <json field>=<task field>", pair);
+        };
+        map.push((path.trim().to_string(), Field::parse(field.trim())?));
+    }
+    if !map.iter().any(|(_, f)| *f == Field::Description) {
+        anyhow::bail!("The mapping needs a field for description, e.g. --map 'title=description'");
+    }
+    Ok(map)
+}
+
+fn lookup<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(item, |value, key| value.get(key)).filter(|v| !v.is_null())
+}
+
+// Массив задач: весь файл или первый массив объектов на верхнем уровне ({"reminders": [...]})
+fn items(root: &Value) -> Result<&Vec<Value>> {
+    match root {
+        Value::Array(items) => Ok(items),
+        Value::Object(fields) => fields.values()
+        .find_map(|v| v.as_array().filter(|a| a.iter().any(Value::is_object)))
+        .context("No array of tasks found in the JSON"),
+        _ => anyhow::bail!("Expected a JSON array of tasks"),
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn as_bool(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => matches!(s.to_lowercase().as_str(), "true" | "yes" | "1" | "done" | "completed" | "x"),
+        _ => false,
+    }
+}
+
+// Строки как в @due(...) и ISO 8601, числа - unix-время в секундах или миллисекундах
+fn as_time(value: &Value, time: &TimeSettings) -> Result<DateTime<Utc>> {
+    match value {
+        Value::Number(n) => {
+            let secs = n.as_i64().context("time must be a whole number")?;
+            let secs = if secs > 100_000_000_000 { secs / 1000 } else { secs };
+            DateTime::from_timestamp(secs, 0).context("time is out of range")
+        }
+        Value::String(s) => match DateTime::parse_from_rfc3339(s.trim()) {
+            Ok(t) => Ok(t.with_timezone(&Utc)),
+            Err(_) => parse_due(s.trim(), time),
+        },
+        other => anyhow::bail!("can't read {} as a time", other),
+    }
+}
+
+// Числовой приоритет как у Apple и iCalendar: 1-4 высокий, 5 средний, 6-9 низкий, 0 - нет
+fn as_priority(value: &Value) -> Result<Option<Priority>> {
+    match value {
+        Value::Number(n) => Ok(match n.as_u64() {
+            Some(1..=4) => Some(Priority::High),
+            Some(5) => Some(Priority::Medium),
+            Some(6..=9) => Some(Priority::Low),
+            _ => None,
+        }),
+        Value::String(s) if s.trim().is_empty() || s == "none" => Ok(None),
+        Value::String(s) => Priority::from_str(s.trim(), true)
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("unknown priority '{}'", s)),
+        other => anyhow::bail!("can't read {} as a priority", other),
+    }
+}
+
+fn as_status(value: &Value) -> Result<Status> {
+    let text = as_text(value).to_lowercase();
+    Ok(match text.as_str() {
+        "todo" | "open" | "pending" | "" => Status::Todo,
+        "done" | "completed" | "complete" => Status::Done,
+        "started" | "in_progress" | "in progress" | "doing" => Status::InProgress,
+        "waiting" | "blocked" => Status::Waiting,
+        "cancelled" | "canceled" | "deleted" => Status::Cancelled,
+        _ => anyhow::bail!("unknown status '{}'", text),
+    })
+}
+
+fn as_tags(value: &Value) -> Vec<String> {
+    let words: Vec<String> = match value {
+        Value::Array(items) => items.iter().map(as_text).collect(),
+        other => as_text(other).split([',', ' ']).map(str::to_string).collect(),
+    };
+    words.iter().map(|w| w.trim().trim_start_matches(['#', '+']).to_string()).filter(|w| !w.is_empty()).collect()
+}
+
+// Задача и её сессия (None - сессия по умолчанию) из одного объекта
+fn task_from(item: &Value, map: &[(String, Field)], time: &TimeSettings) -> Result<(Option<String>, Task)> {
+    let mut task = Task { created_at: Some(Utc::now()), ..Default::default() };
+    let mut session = None;
+    for (path, field) in map {
+        let Some(value) = lookup(item, path) else {
+            continue;
+        };
+        match field {
+            Field::Description => task.description = as_text(value),
+            Field::Time => task.time = Some(as_time(value, time).with_context(|| format!("bad {}", path))?),
+            Field::Session => session = Some(as_text(value)).filter(|s| !s.is_empty()),
+            Field::Done if as_bool(value) => task.status = Status::Done,
+            Field::Done => {}
+            Field::Status => task.status = as_status(value)?,
+            Field::Details => task.details = Some(as_text(value)).filter(|d| !d.is_empty()),
+            Field::Tags => task.tags = as_tags(value),
+            Field::Priority => task.priority = as_priority(value)?,
+            Field::Location => task.location = Some(as_text(value)).filter(|l| !l.is_empty()),
+        }
+    }
+    if task.description.is_empty() {
+        anyhow::bail!("empty task description");
+    }
+    if task.status == Status::Done {
+        task.completed_at = task.created_at;
+    }
+    Ok((session, task))
+}
+
+pub fn import_json(
+    content: &str,
+    map: &[(String, Field)],
+    default_session: &str,
+    sessions: &mut HashMap<String, Vec<Task>>,
+    time: &TimeSettings,
+) -> Result<ImportReport> {
+    let root: Value = serde_json::from_str(content).context("Not valid JSON")?;
+    let mut report = ImportReport::default();
+
+    for (i, item) in items(&root)?.iter().enumerate() {
+        match task_from(item, map, time) {
+            Ok((session, task)) => {
+                let tasks = sessions.entry(session.unwrap_or_else(|| default_session.to_string())).or_default();
+                merge_task(tasks, task, &mut report);
+            }
+            Err(e) => report.failed.push((format!("item {}", i + 1), format!("{:#}", e))),
+        }
+    }
+
+    for tasks in sessions.values_mut() {
+        sort_tasks(tasks);
+    }
+    Ok(report)
+}
+
+const TODO_MARKER: &str = "TODO(ttd):";
+const SKIPPED_DIRS: [&str; 3] = ["target", "node_modules", "vendor"];
+
+// Ищет "TODO(ttd): текст" в исходниках. Задача узнаётся по тексту, место в коде
+// хранится вложением "файл:строка" и обновляется, если комментарий переехал
+pub fn scan_todos(root: &Path, tasks: &mut Vec<Task>) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    // Полные пути: вложение должно открываться из любого каталога
+    let root = root.canonicalize().with_context(|| format!("Cannot read {}", root.display()))?;
+    let mut files = Vec::new();
+    collect_files(&root, &mut files)?;
+    files.sort();
+
+    for file in files {
+        // Бинарные и не-UTF-8 файлы молча пропускаем
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        if content.contains('\0') {
+            continue;
+        }
+        let file = file.display().to_string();
+
+        for (line_no, line) in content.lines().enumerate() {
+            let Some(pos) = line.find(TODO_MARKER) else {
+                continue;
+            };
+            let place = format!("{}:{}", file, line_no + 1);
+            let description = line[pos + TODO_MARKER.len()..]
+            .trim()
+            .trim_end_matches("*/")
+            .trim_end_matches("-->")
+            .trim()
+            .to_string();
+
+            if description.is_empty() {
+                report.failed.push((place, "empty TODO(ttd) comment".to_string()));
+                continue;
+            }
+
+            match find_same(tasks, &description) {
+                Some(task) if task.attachments.contains(&place) => {
+                    report.skipped += 1;
+                    report.duplicates.push(format!("skipped {}: already at {}", existing_label(task, &description), place));
+                }
+                Some(task) => {
+                    let prefix = format!("{}:", file);
+                    task.attachments.retain(|a| !a.starts_with(&prefix));
+                    report.duplicates.push(format!("merged {}: now at {}", existing_label(task, &description), place));
+                    task.attachments.push(place);
+                    report.merged += 1;
+                }
+                None => {
+                    tasks.push(Task {
+                        description,
+                        attachments: vec![place],
+                        created_at: Some(Utc::now()),
+                        ..Default::default()
+                    });
+                    report.added += 1;
+                }
+            }
+        }
+    }
+
+    sort_tasks(tasks);
+    Ok(report)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_files(&entry.path(), files)?;
+            }
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use std::io::IsTerminal;
+use std::process::Command;
+
+const MAX_LABEL_LEN: usize = 32;
+
+// Возвращает (начало, конец) каждой ссылки http(s):// в тексте
+pub fn find_urls(text: &str) -> Vec<(usize, usize)> {
+    let mut urls = Vec::new();
+    let mut pos = 0;
+
+    while let Some(found) = ["https://", "http://"].iter().filter_map(|p| text[pos..].find(p)).min() {
+        let start = pos + found;
+        let end = text[start..]
+        .find(char::is_whitespace)
+        .map_or(text.len(), |len| start + len);
+        let trimmed = text[start..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '\'', '"']);
+        let end = start + trimmed.len();
+
+        if trimmed.len() > "https://".len() {
+            urls.push((start, end));
+        }
+        pos = end.max(start + 1);
+    }
+    urls
+}
+
+pub fn first_url(text: &str) -> Option<&str> {
+    find_urls(text).first().map(|&(start, end)| &text[start..end])
+}
+
+// "https://github.com/Valirum/ttd/issues/12" -> "github.com/…/issues/12"
+pub fn shorten(url: &str) -> String {
+    let bare = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let bare = bare.strip_prefix("www.").unwrap_or(bare).trim_end_matches('/');
+    if bare.chars().count() <= MAX_LABEL_LEN {
+        return bare.to_string();
+    }
+
+    let host = bare.split('/').next().unwrap_or(bare);
+    let tail_len = MAX_LABEL_LEN.saturating_sub(host.chars().count() + 2);
+    let tail: String = {
+        let chars: Vec<char> = bare.chars().collect();
+        chars[chars.len() - tail_len.min(chars.len())..].iter().collect()
+    };
+    format!("{}/…{}", host, tail)
+}
+
+pub fn supports_hyperlinks() -> bool {
+    if let Ok(value) = std::env::var("TTD_HYPERLINKS") {
+        return value != "0";
+    }
+    if !std::io::stdout().is_terminal() || std::env::var("TERM").is_ok_and(|t| t == "dumb") {
+        return false;
+    }
+    ["VTE_VERSION", "WT_SESSION", "KITTY_WINDOW_ID", "WEZTERM_EXECUTABLE", "KONSOLE_VERSION"]
+    .iter()
+    .any(|var| std::env::var_os(var).is_some())
+    || std::env::var("TERM_PROGRAM").is_ok_and(|p| {
+        matches!(p.as_str(), "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper")
+    })
+}
+
+// OSC 8: кликабельная ссылка с подписью label
+pub fn hyperlink(url: &str, label: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, label)
+}
+
+// Подчёркнутые синие ссылки внутри описания, остальной текст без изменений.
+// Сокращаем ссылку, только если терминал умеет OSC 8, иначе адрес потеряется
+pub fn decorate(text: &str, osc8: bool) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+
+    for (start, end) in find_urls(text) {
+        let url = &text[start..end];
+        out.push_str(&text[last..start]);
+        out.push_str("\x1b[4;34m");
+        if osc8 {
+            out.push_str(&hyperlink(url, &shorten(url)));
+        } else {
+            out.push_str(url);
+        }
+        out.push_str("\x1b[24;39m");
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+pub fn open_url(url: &str) -> Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+
+    let status = Command::new(opener)
+    .arg(url)
+    .status()
+    .with_context(|| format!("Failed to run '{}'", opener))?;
+    if !status.success() {
+        anyhow::bail!("'{}' exited with {}", opener, status);
+    }
+    Ok(())
+}
@@ -1,27 +1,241 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand, ValueHint};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 use xdg::BaseDirectories;
-use chrono::{DateTime, Utc, TimeDelta, Weekday, Datelike, NaiveDate, Duration};
+use chrono::{DateTime, Utc, TimeDelta, Weekday, Datelike, NaiveDate, NaiveTime, Timelike, Duration};
 use humantime::parse_duration;
 use strsim::jaro_winkler;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-use std::io::Write;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+use std::io::{IsTerminal, Read, Write};
 
-#[derive(Deserialize)]
+mod accessibility;
+mod confirm;
+mod countdown;
+mod delta;
+mod errors;
+mod export;
+mod filter;
+mod fsck;
+mod gantt;
+mod holidays;
+mod githook;
+mod history;
+mod import;
+mod links;
+mod mirror;
+mod numbering;
+mod porcelain;
+mod profile;
+mod recurrence;
+mod report;
+mod search;
+mod selftest;
+mod serve;
+mod storage;
+mod tags;
+mod template;
+mod theme;
+mod track;
+mod trash;
+mod undo;
+mod upgrade;
+mod vacation;
+
+#[derive(Deserialize, Default)]
 struct Config {
+    #[serde(default)]
     app: AppConfig,
+    auto_export: Option<AutoExportConfig>,
+    #[serde(default)]
+    times: HashMap<String, String>,
+    #[serde(default)]
+    holidays: holidays::HolidayConfig,
+    #[serde(default)]
+    templates: BTreeMap<String, template::Template>,
+    #[serde(default)]
+    confirm: confirm::ConfirmConfig,
+    #[serde(default)]
+    import_maps: BTreeMap<String, String>,
+    #[serde(default)]
+    sessions: BTreeMap<String, SessionConfig>,
+}
+
+// [sessions.personal] с sync = false: сессия остаётся на этой машине,
+// её не выгружают export, auto_export, serve-ics и зеркало
+#[derive(Deserialize)]
+struct SessionConfig {
+    #[serde(default = "default_sync")]
+    sync: bool,
 }
 
 #[derive(Deserialize)]
 struct AppConfig {
-    timezone_offset_hours: i64,
+    // "UTC+3"; timezone_offset_hours = 3 - старое написание того же
+    timezone: Option<String>,
+    timezone_offset_hours: Option<i64>,
+    #[serde(default = "default_can_override")]
     can_override: bool,
     exact_match_threshold: Option<f64>,
     strict_comparison: Option<bool>,
+    #[serde(default)]
+    sort_by_priority: bool,
+    #[serde(default)]
+    session_from_git: bool,
+    #[serde(default)]
+    git_session_per_branch: bool,
+    #[serde(default)]
+    auto_complete_parent: bool,
+    #[serde(default)]
+    git_hook_blocks: bool,
+    #[serde(default)]
+    week_starts: WeekStart,
+    #[serde(default)]
+    theme: theme::Theme,
+    #[serde(default)]
+    default_command: DefaultCommand,
+    mirror: Option<String>,
+    #[serde(default)]
+    delta_save: bool,
+    #[serde(default = "default_pretty_json")]
+    pretty_json: bool,
+    #[serde(default)]
+    compress: bool,
+}
+
+fn default_timezone_offset() -> i64 { 3 }
+
+// "UTC+3", "UTC-5", "+03:00", "UTC" -> смещение в часах
+fn parse_timezone(text: &str) -> Result<i64> {
+    let invalid = || anyhow::anyhow!("Invalid timezone '{}' in [app], expected e.g. \"UTC+3\"", text);
+    let offset = text.trim().trim_start_matches("UTC").trim_start_matches("GMT");
+    if offset.is_empty() {
+        return Ok(0);
+    }
+    let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "00"));
+    if minutes != "00" {
+        anyhow::bail!("Timezone '{}': only whole hours are supported", text);
+    }
+    let hours: i64 = hours.parse().map_err(|_| invalid())?;
+    if !offset.starts_with(['+', '-']) || hours.abs() > 14 {
+        return Err(invalid());
+    }
+    Ok(hours)
+}
+fn default_can_override() -> bool { true }
+fn default_pretty_json() -> bool { true }
+fn default_sync() -> bool { true }
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            timezone: None,
+            timezone_offset_hours: None,
+            can_override: default_can_override(),
+            exact_match_threshold: None,
+            strict_comparison: None,
+            sort_by_priority: false,
+            session_from_git: false,
+            git_session_per_branch: false,
+            auto_complete_parent: false,
+            git_hook_blocks: false,
+            week_starts: WeekStart::Monday,
+            theme: theme::Theme::Default,
+            default_command: DefaultCommand::Summary,
+            mirror: None,
+            delta_save: false,
+            pretty_json: default_pretty_json(),
+            compress: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AutoExportConfig {
+    format: export::Format,
+    path: String,
+    #[serde(default)]
+    on: AutoExportTrigger,
+    filter: Option<String>,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum AutoExportTrigger {
+    #[default]
+    Change,
+    Always,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+// Что делать, если ttd запущен без команды
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum DefaultCommand {
+    #[default]
+    Summary,
+    L,
+    #[serde(alias = "overdue")]
+    Due,
+    None,
+}
+
+// Всё, что нужно для разбора и показа времени
+struct TimeSettings {
+    offset_hours: i64,
+    week_start: Weekday,
+    named_times: HashMap<String, NaiveTime>,
+    holidays: holidays::Holidays,
+}
+
+impl TimeSettings {
+    fn new(offset_hours: i64, week_starts: WeekStart, overrides: &HashMap<String, String>, holidays: holidays::Holidays) -> Result<TimeSettings> {
+        let mut named_times = HashMap::new();
+        for (name, hour) in [("morning", 9), ("noon", 12), ("evening", 18), ("night", 22)] {
+            named_times.insert(name.to_string(), NaiveTime::from_hms_opt(hour, 0, 0).unwrap());
+        }
+        for (name, value) in overrides {
+            let time = NaiveTime::parse_from_str(value, "%H:%M")
+            .with_context(|| format!("Invalid time '{}' for '{}' in [times], expected HH:MM", value, name))?;
+            named_times.insert(name.to_lowercase(), time);
+        }
+        let week_start = match week_starts {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        };
+        Ok(TimeSettings { offset_hours, week_start, named_times, holidays })
+    }
+}
+
+struct Settings {
+    time: TimeSettings,
+    can_override: bool,
+    match_threshold: f64,
+    strict_comparison: bool,
+    sort_by_priority: bool,
+    // None - не брать сессию из git; Some(true) - отдельная сессия на каждую ветку
+    git_session: Option<bool>,
+    auto_complete_parent: bool,
+    git_hook_blocks: bool,
+    theme: theme::Theme,
+    default_command: DefaultCommand,
+    mirror: Option<PathBuf>,
+    delta_save: bool,
+    storage: storage::Format,
+    templates: BTreeMap<String, template::Template>,
+    confirm: confirm::ConfirmConfig,
+    import_maps: BTreeMap<String, String>,
+    auto_export: Option<AutoExportConfig>,
+    local_sessions: Vec<String>,
 }
 
 #[derive(Parser)]
@@ -32,8 +246,20 @@ about,
 long_about,
 )]
 struct Cli {
+    #[arg(long, global = true)]
+    plain: bool,
+    #[arg(long, global = true)]
+    porcelain: bool,
+    #[arg(long, global = true)]
+    profile: bool,
+    #[arg(long, global = true)]
+    no_interactive: bool,
+    #[arg(long, global = true, value_enum, default_value_t = errors::ErrorFormat::Human)]
+    error_format: errors::ErrorFormat,
+    #[arg(short = 's', long = "use-session", global = true)]
+    use_session: Option<String>,
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
@@ -43,42 +269,806 @@ enum Commands {
     #[command(visible_alias = "session")]
     S { session: Option<String> },
     #[command(visible_alias = "add")]
-    A { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    A {
+        #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String>,
+        #[arg(long, num_args(0..=1), default_missing_value = "")] details: Option<String>,
+        #[arg(long)] template: Option<String>,
+    },
+    #[command(visible_alias = "edit")]
+    E { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "duplicate")]
+    Dup {
+        #[arg(num_args(1..), value_hint = ValueHint::CommandString)]
+        parts: Vec<String>,
+        #[arg(long)]
+        to: Option<String>,
+    },
+    #[command(visible_alias = "move")]
+    Mv {
+        query: String,
+        session: String,
+        #[arg(long)]
+        copy: bool,
+    },
+    #[command(visible_alias = "subtask")]
+    Sub {
+        parent: String,
+        #[arg(num_args(1..), value_hint = ValueHint::CommandString)]
+        parts: Vec<String>,
+    },
+    #[command(visible_alias = "note")]
+    N {
+        query: String,
+        #[arg(long)]
+        show: bool,
+        #[arg(long, conflicts_with = "show")]
+        clear: bool,
+    },
+    #[command(visible_alias = "depend")]
+    Dep { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    Pin { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    Unpin { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    Lock { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    Unlock { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
     #[command(visible_alias = "remove")]
     R { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
     #[command(visible_alias = "remove-session")]
     Rs { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
     #[command(visible_alias = "done")]
-    D { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    D {
+        #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String>,
+        #[arg(long)] force: bool,
+    },
     #[command(visible_alias = "undone")]
     Ud { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "snooze")]
+    Sn {
+        #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String>,
+        #[arg(long)] overdue: bool,
+    },
+    #[command(visible_alias = "start")]
+    St { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "wait")]
+    Wt { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "cancel")]
+    Cn { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "skip")]
+    Sk { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
     #[command(visible_alias = "time")]
     T { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
     #[command(visible_alias = "list")]
-    L,
+    L {
+        #[arg(long)]
+        near: Option<String>,
+        #[arg(long)]
+        easy: bool,
+        #[arg(long)]
+        big: bool,
+        #[arg(long)]
+        speak: bool,
+        #[arg(long)]
+        by_priority: bool,
+        #[arg(long)]
+        tag: Vec<String>,
+        #[arg(long)]
+        long: bool,
+        #[arg(long)]
+        all: bool,
+        #[command(flatten)]
+        filter: filter::Flags,
+    },
     #[command(visible_alias = "list-all")]
-    Ll,
+    Ll {
+        #[arg(long)]
+        tag: Vec<String>,
+        #[arg(long)]
+        all: bool,
+        #[command(flatten)]
+        filter: filter::Flags,
+    },
+    #[command(visible_alias = "export")]
+    Ex {
+        format: export::Format,
+        #[arg(long)]
+        filter: Option<String>,
+        #[arg(long)]
+        session: Vec<String>,
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(short, long)]
+        all: bool,
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_hint = ValueHint::CommandString)]
+        command: Vec<String>,
+    },
+    ServeIcs {
+        #[arg(long)]
+        filter: Option<String>,
+        #[arg(long)]
+        session: Vec<String>,
+        #[arg(short, long)]
+        all: bool,
+        #[arg(long, default_value = "127.0.0.1:8765")]
+        bind: String,
+    },
+    #[command(visible_alias = "context")]
+    Ctx {
+        contexts: Vec<String>,
+        #[arg(long, conflicts_with = "contexts")]
+        clear: bool,
+    },
+    #[command(visible_alias = "location")]
+    Loc {
+        query: String,
+        #[arg(value_hint = ValueHint::Other)]
+        location: Vec<String>,
+    },
+    #[command(visible_alias = "difficulty")]
+    Dif {
+        query: String,
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=MAX_DIFFICULTY as i64))]
+        level: u8,
+    },
+    #[command(visible_alias = "priority")]
+    P {
+        query: String,
+        level: Option<Priority>,
+        #[arg(long, conflicts_with = "level")]
+        clear: bool,
+    },
+    #[command(visible_alias = "estimate")]
+    Est {
+        query: String,
+        #[arg(value_hint = ValueHint::Other)]
+        estimate: Vec<String>,
+        #[arg(long, conflicts_with = "estimate")]
+        clear: bool,
+    },
+    #[command(visible_alias = "load")]
+    Ld,
+    #[command(visible_alias = "forecast")]
+    Fc { span: Option<String> },
+    Stats {
+        #[arg(long)]
+        accuracy: bool,
+    },
+    #[command(visible_alias = "vacation")]
+    Vac {
+        range: Option<String>,
+        #[arg(long, conflicts_with = "range")]
+        clear: bool,
+    },
+    #[command(visible_alias = "delegate")]
+    Dg { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "schedule")]
+    Sch { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "defer")]
+    Df { #[arg(num_args(1..), value_hint = ValueHint::CommandString)] parts: Vec<String> },
+    #[command(visible_alias = "overdue")]
+    Od {
+        #[command(subcommand)]
+        command: OverdueCommand,
+    },
+    #[command(visible_alias = "search")]
+    Sr {
+        #[arg(num_args(1..))]
+        query: Vec<String>,
+        #[arg(long)]
+        regex: bool,
+    },
+    #[command(visible_alias = "batch")]
+    B,
+    #[command(visible_alias = "open")]
+    O {
+        query: String,
+        #[arg(long)]
+        editor: bool,
+    },
+    #[command(visible_alias = "link")]
+    Lk {
+        query: String,
+        #[arg(value_hint = ValueHint::Url)]
+        urls: Vec<String>,
+        #[arg(long, conflicts_with = "urls")]
+        clear: bool,
+    },
+    #[command(visible_alias = "present")]
+    Pr { session: Option<String> },
+    #[command(visible_alias = "gantt")]
+    Gt {
+        session: Option<String>,
+        #[arg(long, default_value_t = 28, value_parser = clap::value_parser!(u32).range(1..=366))]
+        days: u32,
+    },
+    #[command(visible_alias = "next")]
+    Nx,
+    #[command(visible_alias = "when")]
+    Wh { #[arg(num_args(1..), value_hint = ValueHint::Other)] parts: Vec<String> },
+    #[command(visible_alias = "countdown")]
+    Cd {
+        query: String,
+        #[arg(long, value_name = "DURATION")]
+        flash: Option<String>,
+    },
+    Pomo {
+        query: String,
+        #[arg(default_value = "25m")]
+        length: String,
+        // Заполняет run(), когда отсчёт уже прошёл: id задачи, начало и название
+        #[arg(skip)]
+        waited: Option<(u64, DateTime<Utc>, String)>,
+    },
+    #[command(visible_alias = "track")]
+    Tr {
+        #[command(subcommand)]
+        command: TrackCommand,
+    },
+    Tags {
+        #[arg(long, hide = true, allow_hyphen_values = true)]
+        complete: Option<String>,
+    },
+    #[command(visible_alias = "tag")]
+    Tg {
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    Restore { query: String },
+    #[command(visible_alias = "history")]
+    Hist { query: String },
+    Undo {
+        #[arg(long)]
+        list: bool,
+    },
+    Redo,
+    #[command(visible_alias = "template")]
+    Tpl {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+    #[command(visible_alias = "git-hook")]
+    Gh {
+        #[command(subcommand)]
+        command: GitHookCommand,
+    },
+    Fsck {
+        #[arg(long)]
+        repair: bool,
+    },
+    Selftest,
+    #[command(visible_alias = "scan")]
+    Sc {
+        #[arg(default_value = ".", value_hint = ValueHint::AnyPath)]
+        path: PathBuf,
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    #[command(visible_alias = "import")]
+    Im {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+}
+
+#[derive(Subcommand)]
+enum OverdueCommand {
+    Report,
+}
+
+#[derive(Subcommand)]
+enum TrackCommand {
+    Start { query: String },
+    Stop,
+    Report {
+        #[arg(long, value_enum, default_value_t = track::Group::Task)]
+        by: track::Group,
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagCommand {
+    Add {
+        tag: String,
+        #[arg(num_args(0..), value_hint = ValueHint::CommandString)]
+        parts: Vec<String>,
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    Rm {
+        tag: String,
+        #[arg(num_args(0..), value_hint = ValueHint::CommandString)]
+        parts: Vec<String>,
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    Rename { from: String, to: String },
+    Merge {
+        #[arg(num_args(2..))]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    #[command(visible_alias = "ls")]
+    List,
+    Empty {
+        #[arg(long)]
+        older_than: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    Save {
+        name: String,
+        #[arg(num_args(1..), value_hint = ValueHint::CommandString)]
+        parts: Vec<String>,
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+    },
+    #[command(visible_alias = "ls")]
+    List,
+    Rm { name: String },
+}
+
+#[derive(Subcommand)]
+enum GitHookCommand {
+    Install {
+        #[arg(long)]
+        force: bool,
+    },
+    Check,
+}
+
+#[derive(Subcommand)]
+enum ImportSource {
+    Checklist {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Json {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        #[arg(long)]
+        map: String,
+        #[arg(long)]
+        session: Option<String>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+const MAX_DIFFICULTY: u8 = 5;
+const EASY_DIFFICULTY: u8 = 2;
+// Отложенные задачи в l --all: приглушённый сиреневый, не путается со статусами
+const DEFERRED_COLOR: Color = Color::Ansi256(103);
+
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum Priority {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Priority {
+    fn label(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Critical => "critical",
+        }
+    }
+
+    fn color(self) -> Color {
+        let palette = theme::palette();
+        match self {
+            Priority::Low => palette.priority_low,
+            Priority::Medium => palette.priority_medium,
+            Priority::High | Priority::Critical => palette.priority_high,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    #[default]
+    Todo,
+    InProgress,
+    Waiting,
+    Done,
+    Cancelled,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in progress",
+            Status::Waiting => "waiting",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+        }
+    }
+
+    // Колонка статуса в списке, всегда 6 символов
+    fn tag(self) -> &'static str {
+        match self {
+            Status::Todo => "[TODO]",
+            Status::InProgress => "[PROG]",
+            Status::Waiting => "[WAIT]",
+            Status::Done => "[DONE]",
+            Status::Cancelled => "[CNCL]",
+        }
+    }
+
+    fn is_open(self) -> bool {
+        !matches!(self, Status::Done | Status::Cancelled)
+    }
+
+    // Старые файлы хранят "done": true/false вместо статуса
+    fn deserialize_compat<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Status, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Done(bool),
+            Status(Status),
+        }
+        Ok(match Stored::deserialize(deserializer)? {
+            Stored::Done(true) => Status::Done,
+            Stored::Done(false) => Status::Todo,
+            Stored::Status(status) => status,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 struct Task {
+    // Постоянный номер задачи, не меняется при сортировке; нужен для зависимостей
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
     description: String,
     time: Option<DateTime<Utc>>,
-    done: bool,
+    // Закреплённые задачи всегда в начале списка
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+    // Запертую задачу нельзя менять, пока не сделан unlock
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    locked: bool,
+    // Конец для событий "at 14h for 2h"; просрочка считается по нему
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<DateTime<Utc>>,
+    #[serde(default, alias = "done", deserialize_with = "Status::deserialize_compat")]
+    status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    difficulty: Option<u8>,
+    // Оценка в минутах
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimate: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delegate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    followup: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheduled: Option<DateTime<Utc>>,
+    // До этого момента задача не видна в l без --all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deferred: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurrence: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    subtasks: Vec<Task>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    blocked_by: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    intervals: Vec<track::Interval>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pomodoros: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TrashEntry {
+    session: String,
+    task: Task,
+    deleted_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 struct Data {
     current_session: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous_session: Option<String>,
     sessions: HashMap<String, Vec<Task>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    active_contexts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vacation: Option<vacation::Vacation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    trash: Vec<TrashEntry>,
+    #[serde(default)]
+    next_id: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    templates: BTreeMap<String, template::Template>,
+    // Хеш последней записи зеркала: по нему видно, правили ли файл руками
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mirror_hash: Option<u64>,
+    // Версия ttd, которая последней писала файл: по ней видно обновление
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+impl Data {
+    // Выдаёт id задачам, у которых его ещё нет (новые и из старых файлов)
+    fn assign_ids(&mut self) {
+        let mut names: Vec<&String> = self.sessions.keys().collect();
+        names.sort();
+        let names: Vec<String> = names.into_iter().cloned().collect();
+        for name in names {
+            for task in self.sessions.get_mut(&name).unwrap() {
+                if task.id.is_none() {
+                    self.next_id += 1;
+                    task.id = Some(self.next_id);
+                }
+            }
+        }
+    }
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl Task {
+    fn is_open(&self) -> bool {
+        self.status.is_open()
+    }
+
+    fn is_deferred(&self, now: DateTime<Utc>) -> bool {
+        self.is_open() && self.deferred.is_some_and(|d| d > now)
+    }
+
+    fn short_id(&self) -> Option<String> {
+        self.id.map(short_id)
+    }
+
+    // (закрыто, всего) по подзадачам; None, если подзадач нет
+    fn progress(&self) -> Option<(usize, usize)> {
+        let total = self.subtasks.len();
+        (total > 0).then(|| (self.subtasks.iter().filter(|t| !t.is_open()).count(), total))
+    }
+
+    // Делегированная задача ждёт, даже если статус не меняли
+    fn effective_status(&self) -> Status {
+        match self.status {
+            Status::Todo if self.delegate.is_some() => Status::Waiting,
+            status => status,
+        }
+    }
+
+    // Срок, после которого задача просрочена: конец события или само время
+    fn deadline(&self) -> Option<DateTime<Utc>> {
+        self.end.or(self.time)
+    }
+
+    // Переносит начало, сохраняя длительность события
+    fn move_to(&mut self, time: Option<DateTime<Utc>>) {
+        self.end = match (self.time, self.end, time) {
+            (Some(start), Some(end), Some(time)) => Some(time + (end - start)),
+            _ => None,
+        };
+        self.time = time;
+    }
+
+    // Копия для dup: суть задачи и время без состояния - статуса, id, учёта времени, повтора
+    fn duplicate(&self) -> Task {
+        Task {
+            description: self.description.clone(),
+            time: self.time,
+            end: self.end,
+            details: self.details.clone(),
+            location: self.location.clone(),
+            difficulty: self.difficulty,
+            estimate: self.estimate,
+            priority: self.priority,
+            tags: self.tags.clone(),
+            subtasks: self.subtasks.iter().map(Task::duplicate).collect(),
+            attachments: self.attachments.clone(),
+            created_at: Some(Utc::now()),
+            ..Default::default()
+        }
+    }
+
+    // Контексты GTD: слова вида @home в описании
+    fn contexts(&self) -> impl Iterator<Item = &str> {
+        self.description.split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|ctx| ctx.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '_'))
+        .filter(|ctx| !ctx.is_empty())
+    }
+
+    fn is_near(&self, place: &str, threshold: f64) -> bool {
+        let Some(location) = &self.location else {
+            return false;
+        };
+        let location = location.to_lowercase();
+        let place = place.to_lowercase();
+        location.contains(&place)
+        || jaro_winkler(&location, &place) >= threshold
+        || location.split(|c: char| !c.is_alphanumeric())
+        .any(|word| !word.is_empty() && jaro_winkler(word, &place) >= threshold)
+    }
+
+    fn in_contexts(&self, contexts: &[String]) -> bool {
+        contexts.is_empty() || self.contexts().any(|ctx| contexts.iter().any(|c| c.eq_ignore_ascii_case(ctx)))
+    }
+
+    // Индексы незавершённых задач сессии, от которых зависит эта
+    fn open_blockers(&self, tasks: &[Task]) -> Vec<usize> {
+        if !self.is_open() || self.blocked_by.is_empty() {
+            return Vec::new();
+        }
+        tasks.iter()
+        .enumerate()
+        .filter(|(_, t)| t.is_open() && t.id.is_some_and(|id| self.blocked_by.contains(&id)))
+        .map(|(i, _)| i)
+        .collect()
+    }
+
+    fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim_start_matches(['#', '+']))))
+    }
+}
+
+// "#work" или "+work" -> Some("work"); номера вроде "#1" тегами не считаем
+fn as_tag(word: &str) -> Option<&str> {
+    word.strip_prefix(['#', '+']).filter(|tag| tag.starts_with(char::is_alphabetic))
+}
+
+// "!high" -> Some(Priority::High)
+fn as_priority(word: &str) -> Option<Priority> {
+    word.strip_prefix('!').and_then(|p| <Priority as clap::ValueEnum>::from_str(p, true).ok())
+}
+
+// Вынимает слова #tag из текста, возвращает остаток и теги без повторов
+fn split_tags(text: &str, tags: &mut Vec<String>) -> String {
+    let mut rest = Vec::new();
+    for word in text.split_whitespace() {
+        match as_tag(word) {
+            Some(tag) => {
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    tags.push(tag.to_string());
+                }
+            }
+            None => rest.push(word),
+        }
+    }
+    rest.join(" ")
+}
+
+// Как split_tags, но ещё вынимает !priority; последний указанный побеждает
+fn split_markers(text: &str, tags: &mut Vec<String>, priority: &mut Option<Priority>) -> String {
+    let rest: Vec<&str> = text.split_whitespace()
+    .filter(|word| match as_priority(word) {
+        Some(p) => {
+            *priority = Some(p);
+            false
+        }
+        None => true,
+    })
+    .collect();
+    split_tags(&rest.join(" "), tags)
+}
+
+// a 'buy milk in 2h' одним аргументом: время отрезаем по первому ключевому слову,
+// за которым идёт что-то похожее на время, чтобы 'check in with Bob' остался целым
+fn split_inline_time(parts: Vec<String>, time: &TimeSettings) -> Vec<String> {
+    if parts.len() != 1 {
+        return parts;
+    }
+    let words: Vec<&str> = parts[0].split_whitespace().collect();
+    let looks_like_time = |word: &str| {
+        word.starts_with(|c: char| c.is_ascii_digit())
+            || word.strip_prefix('w').is_some_and(|w| w.starts_with(|c: char| c.is_ascii_digit()))
+            || time.named_times.contains_key(&word.to_lowercase())
+    };
+    let start = (1..words.len().saturating_sub(1)).find(|&i| match words[i] {
+        "in" | "at" => looks_like_time(words[i + 1]),
+        "every" => {
+            let rule: Vec<&str> = words[i + 1..].iter().copied().take_while(|&w| w != "expires").collect();
+            recurrence::Rule::parse(words[i + 1]).is_ok() || recurrence::Rule::parse(&rule.join(" ")).is_ok()
+        }
+        "expires" => matches!(words[i + 1], "in" | "at"),
+        _ => false,
+    });
+    match start {
+        Some(start) => std::iter::once(words[..start].join(" "))
+        .chain(words[start..].iter().map(|w| w.to_string()))
+        .collect(),
+        None => parts,
+    }
+}
+
+fn parse_relative_time(input: &str, time: &TimeSettings) -> Result<DateTime<Utc>> {
+    // "3bd" - рабочие дни, считаются отдельно: выходные пропускаются
+    let mut rest = input.to_string();
+    let mut business_days = 0;
+    while let Some(pos) = rest.find("bd") {
+        let start = rest[..pos].rfind(|c: char| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+        business_days += match rest[start..pos].parse::<i64>() {
+            Ok(days) => days,
+            Err(_) => anyhow::bail!("Business days need a number, e.g. in 3bd"),
+        };
+        rest.replace_range(start..pos + 2, " ");
+    }
+    let start = add_business_days(Utc::now(), business_days, time);
+    Ok(start + parse_span(rest.trim())?)
+}
+
+// Пропускает выходные и праздники из [holidays]
+fn add_business_days(from: DateTime<Utc>, days: i64, time: &TimeSettings) -> DateTime<Utc> {
+    let mut result = from;
+    let mut left = days;
+    while left > 0 {
+        result += TimeDelta::days(1);
+        if time.holidays.is_working_day((result + TimeDelta::hours(time.offset_hours)).date_naive()) {
+            left -= 1;
+        }
+    }
+    result
+}
+
+// Предупреждение при назначении задачи на праздник
+fn warn_if_holiday(time: Option<DateTime<Utc>>, settings: &TimeSettings) {
+    let Some(time) = time else {
+        return;
+    };
+    if let Some(name) = settings.holidays.name_at(time, settings.offset_hours) {
+        let date = (time + TimeDelta::hours(settings.offset_hours)).format("%Y-%m-%d");
+        println!("Note: {} is a holiday ({})", date, name);
+    }
 }
 
-fn parse_relative_time(input: &str) -> Result<DateTime<Utc>> {
+// "2h", "1h30m", "90 minutes"
+fn parse_span(input: &str) -> Result<TimeDelta> {
     if let Ok(dur) = parse_duration(input) {
-        return Ok(Utc::now() + TimeDelta::from_std(dur)?);
+        return Ok(TimeDelta::from_std(dur)?);
     }
 
-    let mut dt = Utc::now();
+    let mut dt = TimeDelta::zero();
     let mut i = 0;
     while i < input.len() {
         let mut num = 0u64;
@@ -102,7 +1092,13 @@ fn parse_relative_time(input: &str) -> Result<DateTime<Utc>> {
     Ok(dt)
 }
 
-fn parse_absolute_time(input: &str, offset_hours: i64) -> Result<DateTime<Utc>> {
+// 1 - первый день недели из week_starts
+fn weekday_from_number(num: u32, week_start: Weekday) -> Weekday {
+    (1..num).fold(week_start, |day, _| day.succ())
+}
+
+fn parse_absolute_time(input: &str, time: &TimeSettings) -> Result<DateTime<Utc>> {
+    let offset_hours = time.offset_hours;
     let now_utc = Utc::now();
     let offset = TimeDelta::hours(offset_hours);
     let now_local = now_utc + offset;
@@ -116,6 +1112,30 @@ fn parse_absolute_time(input: &str, offset_hours: i64) -> Result<DateTime<Utc>>
     let mut second: Option<u32> = None;
     let mut weekday_target: Option<u32> = None;
 
+    // Именованное время суток: "morning" -> "9h0m"
+    let expanded = input.split_whitespace()
+    .map(|word| match time.named_times.get(&word.to_lowercase()) {
+        Some(t) => format!("{}h{}m", t.hour(), t.minute()),
+        None => word.to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join(" ");
+
+    // "w42" в начале - ISO-неделя, дальше обычный синтаксис: "w42 5w 18h"
+    let mut input = expanded.as_str();
+    let mut iso_week: Option<u32> = None;
+    if let Some(rest) = input.strip_prefix('w') {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits > 0 {
+            let week: u32 = rest[..digits].parse()?;
+            if !(1..=53).contains(&week) {
+                anyhow::bail!("ISO week must be between 1 and 53");
+            }
+            iso_week = Some(week);
+            input = &rest[digits..];
+        }
+    }
+
     let mut i = 0;
     while i < input.len() {
         let mut num = 0u64;
@@ -136,20 +1156,24 @@ fn parse_absolute_time(input: &str, offset_hours: i64) -> Result<DateTime<Utc>>
                 year = Some(num as i32);
             }
             'M' => {
-                if num < 1 || num > 12 {
+                if !(1..=12).contains(&num) {
                     anyhow::bail!("Month must be between 1 and 12");
                 }
                 month = Some(num as u32);
             }
             'd' => {
-                if num < 1 || num > 31 {
+                if !(1..=31).contains(&num) {
                     anyhow::bail!("Day must be between 1 and 31");
                 }
                 day = Some(num as u32);
             }
             'w' => {
-                if num < 1 || num > 7 {
-                    anyhow::bail!("Weekday must be 1-7 (1=Monday, 7=Sunday)");
+                if !(1..=7).contains(&num) {
+                    anyhow::bail!(
+                        "Weekday must be 1-7 (1={}, 7={})",
+                        time.week_start,
+                        time.week_start.pred()
+                    );
                 }
                 weekday_target = Some(num as u32);
             }
@@ -175,18 +1199,26 @@ fn parse_absolute_time(input: &str, offset_hours: i64) -> Result<DateTime<Utc>>
         }
     }
 
-    if let Some(target_weekday_num) = weekday_target {
+    if let Some(week) = iso_week {
         if day.is_none() {
-            let target_weekday = match target_weekday_num {
-                1 => Weekday::Mon,
-                2 => Weekday::Tue,
-                3 => Weekday::Wed,
-                4 => Weekday::Thu,
-                5 => Weekday::Fri,
-                6 => Weekday::Sat,
-                7 => Weekday::Sun,
-                _ => unreachable!(),
-            };
+            // ISO-неделя всегда начинается с понедельника
+            let target_weekday = weekday_target.map_or(Weekday::Mon, |n| weekday_from_number(n, time.week_start));
+            let current_week = now_naive.iso_week();
+            let mut iso_year = year.unwrap_or(current_week.year());
+            // Неделя этого года уже прошла - берём следующий год
+            if year.is_none() && week < current_week.week() {
+                iso_year += 1;
+            }
+
+            let target_date = NaiveDate::from_isoywd_opt(iso_year, week, target_weekday)
+            .ok_or_else(|| anyhow::anyhow!("Year {} has no ISO week {}", iso_year, week))?;
+            day = Some(target_date.day());
+            month = Some(target_date.month());
+            year = Some(target_date.year());
+        }
+    } else if let Some(target_weekday_num) = weekday_target {
+        if day.is_none() {
+            let target_weekday = weekday_from_number(target_weekday_num, time.week_start);
 
             let current_weekday = now_naive.weekday();
             let mut days_ahead = (target_weekday.num_days_from_monday() as i64) -
@@ -222,177 +1254,774 @@ fn parse_absolute_time(input: &str, offset_hours: i64) -> Result<DateTime<Utc>>
     Ok(utc_dt)
 }
 
-fn get_time_color(time: &Option<DateTime<Utc>>) -> Color {
-    match time {
-        Some(t) if *t < Utc::now() => Color::Red,
-        Some(_) => Color::Yellow,
-        None => Color::Blue,
+// "in 2h" / "at 14h"; None и сообщение пользователю, если префикс неизвестен
+fn parse_prefixed_time(prefix: &str, time_str: &str, time: &TimeSettings) -> Result<Option<DateTime<Utc>>> {
+    match prefix {
+        "in" => Ok(Some(parse_relative_time(time_str, time)?)),
+        "at" => Ok(Some(parse_absolute_time(time_str, time)?)),
+        _ if errors::is_json() => {
+            errors::ErrorReport {
+                suggestion: Some("Use 'in' for relative time or 'at' for absolute time".to_string()),
+                ..errors::ErrorReport::new("unknown_time_prefix", format!("Unknown time prefix '{}'", prefix))
+            }
+            .emit();
+            Ok(None)
+        }
+        _ => {
+            println!("Unknown time prefix '{}'. Use 'in' for relative time or 'at' for absolute time.", prefix);
+            Ok(None)
+        }
     }
 }
 
-fn get_status_color(done: bool) -> Color {
-    if done { Color::Green } else { Color::Yellow }
-}
-
-fn print_formatted_task(i: usize, task: &Task, offset_hours: i64) -> Result<()> {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-    write!(stdout, "  #{:<2}", i)?;
+// Начало и необязательный конец события
+type TimeRange = (DateTime<Utc>, Option<DateTime<Utc>>);
 
-    let status_color = get_status_color(task.done);
-    let status_text = if task.done { "[DONE]" } else { "[TODO]" };
-    let mut status_spec = ColorSpec::new();
-    status_spec.set_fg(Some(status_color));
-    stdout.set_color(&status_spec)?;
-    write!(stdout, "{:<6}", status_text)?;
-    stdout.reset()?;
+// "in 2h", "at 14h", "at 14h for 2h" или "from 14h to 16h"
+fn parse_time_range(parts: &[String], time: &TimeSettings) -> Result<Option<TimeRange>> {
+    if parts[0] == "from" {
+        let Some(to) = parts.iter().position(|p| p == "to").filter(|&to| to > 1 && to + 1 < parts.len()) else {
+            println!("Use 'from <time> to <time>', e.g. from 14h to 16h");
+            return Ok(None);
+        };
+        let start = parse_absolute_time(&parts[1..to].join(" "), time)?;
+        let mut end = parse_absolute_time(&parts[to + 1..].join(" "), time)?;
+        // "from 23h to 1h" - конец на следующий день
+        if end <= start && end + TimeDelta::days(1) > start {
+            end += TimeDelta::days(1);
+        }
+        if end <= start {
+            anyhow::bail!("End time must be after the start time");
+        }
+        return Ok(Some((start, Some(end))));
+    }
 
-    let desc_text = if task.done {
-        format!("\x1b[9m{0}\x1b[0m", task.description)
-    } else {
-        format!("{0}", task.description)
+    let for_pos = parts.iter().position(|p| p == "for").unwrap_or(parts.len());
+    let Some(start) = parse_prefixed_time(&parts[0], &parts[1..for_pos].join(" "), time)? else {
+        return Ok(None);
     };
+    if for_pos == parts.len() {
+        return Ok(Some((start, None)));
+    }
+    let span = parse_span(&parts[for_pos + 1..].join(" "))?;
+    if span <= TimeDelta::zero() {
+        anyhow::bail!("Duration after 'for' must be positive, e.g. for 2h or for 1h30m");
+    }
+    Ok(Some((start, Some(start + span))))
+}
 
-    let time_str = format_time(&task.time, offset_hours);
-    let time_color = get_time_color(&task.time);
+// Время для списка: "[2026-05-04 14:00]" или диапазон "[2026-05-04 14:00 - 16:00]"
+fn format_range(task: &Task, offset_hours: i64) -> String {
+    let start = format_time(&task.time, offset_hours);
+    match (task.time, task.end) {
+        (Some(time), Some(end)) => {
+            let offset = TimeDelta::hours(offset_hours);
+            let same_day = (time + offset).date_naive() == (end + offset).date_naive();
+            let end = (end + offset).format(if same_day { "%H:%M" } else { "%Y-%m-%d %H:%M" });
+            format!("{} - {}]", start.trim_end_matches(']'), end)
+        }
+        _ => start,
+    }
+}
 
-    let mut time_spec = ColorSpec::new();
-    time_spec.set_fg(Some(time_color));
-    stdout.set_color(&time_spec)?;
+fn get_time_color(time: &Option<DateTime<Utc>>) -> Color {
+    let palette = theme::palette();
+    match time {
+        Some(t) if *t < Utc::now() => palette.overdue,
+        Some(_) => palette.upcoming,
+        None => palette.no_time,
+    }
+}
 
-    write!(stdout, "{}", time_str)?;
+fn get_status_color(status: Status) -> Color {
+    let palette = theme::palette();
+    match status {
+        Status::Todo => palette.todo,
+        Status::InProgress => palette.in_progress,
+        Status::Waiting => palette.waiting,
+        Status::Done => palette.done,
+        Status::Cancelled => palette.cancelled,
+    }
+}
 
-    stdout.reset()?;
+// Меньше половины подзадач - как просроченное, дальше как todo, все - как done
+fn progress_color(closed: usize, total: usize) -> Color {
+    let palette = theme::palette();
+    if closed == total {
+        palette.done
+    } else if closed * 2 < total {
+        palette.overdue
+    } else {
+        palette.todo
+    }
+}
 
-    writeln!(stdout, " > {}", desc_text)?;
+struct ListOptions<'a> {
+    offset_hours: i64,
+    vacation: Option<&'a vacation::Vacation>,
+    holidays: &'a holidays::Holidays,
+    big: bool,
+    plain: bool,
+    show_scheduled: bool,
+    // Ширина колонки времени: диапазоны событий длиннее обычного времени
+    time_width: usize,
+    // Ширина колонки тегов в ll; None - теги выводятся после описания
+    tags_column: Option<usize>,
+    // --long: строка с временем создания и выполнения под задачей
+    long: bool,
+    // Задачи той же сессии, чтобы показать зависимости
+    session: &'a [Task],
+}
 
-    stdout.reset()?;
+fn print_subtasks(i: usize, task: &Task, options: &ListOptions) -> Result<()> {
+    let now = Utc::now();
+    for (j, sub) in task.subtasks.iter().enumerate() {
+        if options.plain {
+            println!(
+                "  Subtask {}.{}, {}, {}{}",
+                i,
+                j,
+                sub.status.label(),
+                sub.description,
+                sub.time.map_or(String::new(), |_| format!(", {}", accessibility::describe_due(sub.time, now)))
+            );
+            continue;
+        }
 
+        let mut stdout = StandardStream::stdout(theme::color_choice());
+        let branch = if j + 1 == task.subtasks.len() { "└─" } else { "├─" };
+        write!(stdout, "      {} #{}.{} ", branch, i, j)?;
+        stdout.set_color(&list_color(get_status_color(sub.status), options.big))?;
+        let mark = match sub.status {
+            Status::Done => "[x]",
+            Status::Cancelled => "[-]",
+            Status::InProgress => "[~]",
+            Status::Todo | Status::Waiting => "[ ]",
+        };
+        write!(stdout, "{}", mark)?;
+        stdout.reset()?;
+        if sub.time.is_some() {
+            stdout.set_color(&list_color(get_time_color(&sub.time), options.big))?;
+            write!(stdout, " {}", format_time(&sub.time, options.offset_hours))?;
+            stdout.reset()?;
+        }
+        if !sub.is_open() && !options.big {
+            writeln!(stdout, " \x1b[9m{}\x1b[0m", sub.description)?;
+        } else {
+            writeln!(stdout, " {}", sub.description)?;
+        }
+    }
     Ok(())
 }
 
-fn get_data_path() -> Result<PathBuf> {
-    let xdg = BaseDirectories::with_prefix("ttd")?;
-    let path = xdg.place_config_file("tasks.json")?;
-    Ok(path)
+fn tags_label(tags: &[String]) -> String {
+    tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")
 }
 
-fn get_config_path() -> Result<PathBuf> {
-    let xdg = BaseDirectories::with_prefix("ttd")?;
-    let path = xdg.place_config_file("config.toml")?;
-    Ok(path)
+fn running_on(data: &Data, session: &str, idx: usize) -> bool {
+    track::running(data).is_some_and(|(s, i)| s == session && i == idx)
 }
 
-fn load_config() -> Result<(i64, bool, f64, bool)> {
-    let path = get_config_path()?;
-    let (default_offset, default_override, default_threshold, default_strict) = (3, true, 0.85, false);
+// Вложения бывают ссылками (link) и местами в коде "файл:строка" (scan)
+fn is_url(attachment: &str) -> bool {
+    attachment.starts_with("https://") || attachment.starts_with("http://")
+}
 
-    if path.exists() {
-        let toml_str = fs::read_to_string(&path)?;
-        let config: Config = toml::from_str(&toml_str)?;
-        let threshold = config.app.exact_match_threshold.unwrap_or(default_threshold);
-        let strict = config.app.strict_comparison.unwrap_or(default_strict);
-        Ok((
-            config.app.timezone_offset_hours,
-            config.app.can_override,
-            threshold,
-            strict
-        ))
-    } else {
-        Ok((default_offset, default_override, default_threshold, default_strict))
-    }
+// В режиме --big все цвета жирные и яркие для лучшей контрастности
+fn list_color(color: Color, big: bool) -> ColorSpec {
+    let strong = big || theme::palette().strong;
+    let mut spec = ColorSpec::new();
+    spec.set_fg(Some(color)).set_bold(strong).set_intense(strong);
+    spec
 }
 
-fn load_data() -> Result<Data> {
-    let path = get_data_path()?;
-    let mut data = if path.exists() {
-        let json = fs::read_to_string(&path)?;
-        if json.trim().is_empty() {
-            Data::default()
-        } else {
-            match serde_json::from_str(&json) {
-                Ok(data) => data,
-                Err(_) => Data::default(),
+fn print_formatted_task(i: usize, task: &Task, options: &ListOptions) -> Result<()> {
+    if options.plain {
+        let mut line = accessibility::plain_task_line(i, task, options.offset_hours, Utc::now());
+        let blockers = task.open_blockers(options.session);
+        if !blockers.is_empty() {
+            line.push_str(&format!(", blocked by {}", index_list(&blockers).replace('#', "task ")));
+        }
+        if options.long {
+            for (label, time) in [("created", task.created_at), ("completed", task.completed_at)] {
+                if let Some(time) = time {
+                    line.push_str(&format!(", {} {}", label, format_time(&Some(time), options.offset_hours).trim_matches(['[', ']'])));
+                }
             }
         }
+        println!("{}", line);
+        return Ok(());
+    }
+
+    let offset_hours = options.offset_hours;
+    let big = options.big;
+    let mut stdout = StandardStream::stdout(theme::color_choice());
+    if big {
+        stdout.set_color(&list_color(Color::White, true))?;
+        write!(stdout, "  #{:<3} ", i)?;
     } else {
-        Data::default()
-    };
+        write!(stdout, "  #{:<2}", i)?;
+    }
 
-    for tasks in data.sessions.values_mut() {
-        sort_tasks(tasks);
+    let blockers = task.open_blockers(options.session);
+    let blocked = !blockers.is_empty();
+    let status = task.effective_status();
+    let (status_color, status_text) = if blocked {
+        (Color::Ansi256(244), "[BLKD]")
+    } else {
+        (get_status_color(status), status.tag())
+    };
+    stdout.set_color(&list_color(status_color, big))?;
+    write!(stdout, "{:<6}", status_text)?;
+    stdout.reset()?;
+    if big {
+        write!(stdout, " ")?;
     }
 
-    Ok(data)
-}
+    let description = links::decorate(&task.description, links::supports_hyperlinks());
+    let desc_text = if !task.is_open() && !big {
+        format!("\x1b[9m{0}\x1b[0m", description)
+    } else {
+        description
+    };
 
-fn save_data(data: &Data) -> Result<()> {
-    let path = get_data_path()?;
-    let json = serde_json::to_string_pretty(data)?;
-    fs::create_dir_all(path.parent().unwrap())?;
-    fs::write(&path, json)?;
-    Ok(())
-}
+    let time_str = format_range(task, offset_hours);
+    let on_vacation = options.vacation.is_some_and(|v| task.time.is_some_and(|t| v.contains(t, offset_hours)));
+    let time_color = if on_vacation { Color::Ansi256(244) } else { get_time_color(&task.deadline()) };
 
-fn find_by_index(tasks: &[Task], index: usize) -> Option<usize> {
-    if index < tasks.len() { Some(index) } else { None }
-}
+    stdout.set_color(&list_color(time_color, big))?;
 
-fn find_by_name(tasks: &[Task], query: &str, threshold: f64, strict: bool) -> (Option<usize>, Option<(String, f64)>) {
-    let query_lower = query.to_lowercase();
+    write!(stdout, "{:<width$}", time_str, width = options.time_width)?;
 
-    if strict {
-        let exact_match = tasks.iter().enumerate()
-        .find(|(_, t)| t.description.to_lowercase() == query_lower)
-        .map(|(i, _)| i);
+    stdout.reset()?;
 
-        if let Some(idx) = exact_match {
-            return (Some(idx), None);
+    // Плановая дата - мягкий срок, поэтому никогда не красная
+    if options.show_scheduled {
+        match task.scheduled {
+            Some(scheduled) => {
+                let color = if task.is_open() && scheduled < Utc::now() { Color::Magenta } else { Color::Cyan };
+                stdout.set_color(&list_color(color, big))?;
+                write!(stdout, " ~{}", format_time(&task.scheduled, offset_hours))?;
+                stdout.reset()?;
+            }
+            None => write!(stdout, "  {:18}", "")?,
         }
+    }
 
-        let mut candidates: Vec<(usize, String, f64)> = tasks.iter().enumerate()
-        .map(|(i, t)| {
-            let score = jaro_winkler(&t.description.to_lowercase(), &query_lower);
-            (i, t.description.clone(), score)
-        })
-        .filter(|(_, _, score)| *score > threshold)
-        .collect();
+    if let Some(width) = options.tags_column {
+        stdout.set_color(&list_color(Color::Yellow, big))?;
+        write!(stdout, " {:<width$}", tags_label(&task.tags), width = width)?;
+        stdout.reset()?;
+    }
 
-        candidates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
-
-        if !candidates.is_empty() {
-            let (_idx, desc, score) = (candidates[0].0, candidates[0].1.clone(), candidates[0].2);
-            return (None, Some((desc, score)));
+    if task.pinned {
+        stdout.set_color(&list_color(Color::Magenta, true))?;
+        write!(stdout, " ^ ")?;
+        stdout.reset()?;
+    } else {
+        write!(stdout, " > ")?;
+    }
+    let deferred = task.deferred.filter(|_| task.is_deferred(Utc::now()));
+    if big {
+        stdout.set_color(&list_color(Color::White, true))?;
+    } else if blocked {
+        stdout.set_color(&list_color(Color::Ansi256(244), false))?;
+    } else if deferred.is_some() {
+        stdout.set_color(&list_color(DEFERRED_COLOR, false))?;
+    }
+    write!(stdout, "{}", desc_text)?;
+    stdout.reset()?;
+    if let Some((closed, total)) = task.progress() {
+        stdout.set_color(&list_color(progress_color(closed, total), big))?;
+        write!(stdout, " [{}/{}]", closed, total)?;
+        stdout.reset()?;
+    }
+    if deferred.is_some() {
+        stdout.set_color(&list_color(DEFERRED_COLOR, big))?;
+        write!(stdout, " [deferred until {}]", format_time(&deferred, offset_hours).trim_matches(['[', ']']))?;
+        stdout.reset()?;
+    }
+    if blocked {
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, " [after {}]", index_list(&blockers))?;
+        stdout.reset()?;
+    }
+    if let Some(priority) = task.priority {
+        let mut spec = list_color(priority.color(), big);
+        spec.set_bold(big || priority == Priority::Critical);
+        stdout.set_color(&spec)?;
+        write!(stdout, " [!{}]", priority.label())?;
+        stdout.reset()?;
+    }
+    if task.details.is_some() {
+        stdout.set_color(&list_color(Color::Cyan, big))?;
+        write!(stdout, " [+]")?;
+        stdout.reset()?;
+    }
+    if let Some(delegate) = &task.delegate {
+        stdout.set_color(&list_color(Color::Cyan, big))?;
+        write!(stdout, " [-> {}]", delegate)?;
+        stdout.reset()?;
+    }
+    if let Some(location) = &task.location {
+        stdout.set_color(&list_color(Color::Cyan, big))?;
+        write!(stdout, " ({})", location)?;
+        stdout.reset()?;
+    }
+    // Ссылки показываем каждую, кликабельными если терминал умеет OSC 8
+    let (urls, files): (Vec<&String>, Vec<&String>) = task.attachments.iter().partition(|a| is_url(a));
+    if !urls.is_empty() {
+        let osc8 = links::supports_hyperlinks();
+        for url in urls {
+            stdout.set_color(&list_color(Color::Blue, big))?;
+            if osc8 {
+                write!(stdout, " <{}>", links::hyperlink(url, &links::shorten(url)))?;
+            } else {
+                write!(stdout, " <{}>", url)?;
+            }
+            stdout.reset()?;
+        }
+    }
+    match files.as_slice() {
+        [] => {}
+        [single] => {
+            stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+            write!(stdout, " <{}>", single)?;
+            stdout.reset()?;
+        }
+        many => {
+            stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+            write!(stdout, " <{} attachments>", many.len())?;
+            stdout.reset()?;
+        }
+    }
+    if let Some(holiday) = task.time.filter(|_| task.is_open()).and_then(|t| options.holidays.name_at(t, offset_hours)) {
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, " [holiday: {}]", holiday)?;
+        stdout.reset()?;
+    }
+    if let Some(expires) = task.expires.filter(|_| task.is_open()) {
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, " [expires {}]", format_time(&Some(expires), offset_hours).trim_matches(['[', ']']))?;
+        stdout.reset()?;
+    }
+    if options.tags_column.is_none() && !task.tags.is_empty() {
+        stdout.set_color(&list_color(Color::Yellow, big))?;
+        write!(stdout, " {}", tags_label(&task.tags))?;
+        stdout.reset()?;
+    }
+    if let Some(rule) = &task.recurrence {
+        stdout.set_color(&list_color(Color::Cyan, big))?;
+        write!(stdout, " [every {}{}]", rule, turns_label(rule, task.time, offset_hours))?;
+        stdout.reset()?;
+    }
+    if let Some(level) = task.difficulty {
+        let color = if level <= EASY_DIFFICULTY { Color::Green } else { Color::Magenta };
+        stdout.set_color(&list_color(color, big))?;
+        write!(stdout, " [d{}]", level)?;
+        stdout.reset()?;
+    }
+    if let Some(minutes) = task.estimate {
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, " [est {}]", format_minutes(minutes))?;
+        stdout.reset()?;
+    }
+    if let Some(last) = task.intervals.last() {
+        let spent = format_delta(track::elapsed(&task.intervals, Utc::now()));
+        if last.end.is_none() {
+            stdout.set_color(&list_color(Color::Magenta, big))?;
+            write!(stdout, " [tracking, {}]", spent)?;
+        } else {
+            stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+            write!(stdout, " [tracked {}]", spent)?;
         }
+        stdout.reset()?;
+    }
+    if task.locked {
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, " [locked]")?;
+        stdout.reset()?;
+    }
+    if task.pomodoros > 0 {
+        stdout.set_color(&list_color(Color::Red, big))?;
+        write!(stdout, " [pomo x{}]", task.pomodoros)?;
+        stdout.reset()?;
+    }
+    if let Some(id) = task.short_id() {
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, " ={}", id)?;
+        stdout.reset()?;
+    }
+    writeln!(stdout)?;
+    if options.long {
+        let stamp = |label: &str, time: Option<DateTime<Utc>>| {
+            time.map(|t| format!("{} {}", label, format_time(&Some(t), offset_hours).trim_matches(['[', ']'])))
+        };
+        let stamps: Vec<String> = [stamp("created", task.created_at), stamp("completed", task.completed_at)]
+        .into_iter()
+        .flatten()
+        .collect();
+        stdout.set_color(&list_color(Color::Ansi256(244), big))?;
+        write!(stdout, "       {}", if stamps.is_empty() { "created: unknown".to_string() } else { stamps.join(", ") })?;
+        stdout.reset()?;
+        writeln!(stdout)?;
+    }
+    if big {
+        writeln!(stdout)?;
+    }
+
+    stdout.reset()?;
+
+    Ok(())
+}
+
+// --plain: одна строка с подписями вместо цветной шапки
+fn print_plain_session_header(name: &str, tasks: &[Task], is_current: bool, contexts: &[String]) {
+    let completed = tasks.iter().filter(|t| !t.is_open()).count();
+    let mut line = format!("Session {}", name);
+    if is_current {
+        line.push_str(", current");
+    }
+    line.push_str(&format!(", {} of {} done", completed, tasks.len()));
+    if !contexts.is_empty() {
+        line.push_str(&format!(", context @{}", contexts.join(" @")));
+    }
+    println!("{}", line);
+}
+
+fn print_context_marker(stdout: &mut StandardStream, contexts: &[String]) -> Result<()> {
+    if !contexts.is_empty() {
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+        write!(stdout, " @{}", contexts.join(" @"))?;
+    }
+    Ok(())
+}
+
+// Файл .ttd в текущем каталоге или выше закрепляет за проектом свою сессию:
+// первая непустая строка, не начинающаяся с '#'
+fn find_session_marker() -> Option<(PathBuf, String)> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors().find_map(|dir| {
+        let path = dir.join(".ttd");
+        let content = fs::read_to_string(&path).ok()?;
+        let name = content.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with('#'))?;
+        Some((path, name.to_string()))
+    })
+}
+
+// Имя репозитория (или "репозиторий/ветка") для каталога внутри git
+fn git_session_name(per_branch: bool) -> Option<String> {
+    let cwd = std::env::current_dir().ok()?;
+    let root = cwd.ancestors().find(|dir| dir.join(".git").exists())?;
+    let repo = root.file_name()?.to_string_lossy().to_string();
+    if !per_branch {
+        return Some(repo);
+    }
+
+    // В worktree .git - файл со ссылкой "gitdir: <путь>"
+    let dot_git = root.join(".git");
+    let git_dir = match fs::read_to_string(&dot_git) {
+        Ok(link) => root.join(link.trim().strip_prefix("gitdir:")?.trim()),
+        Err(_) => dot_git,
+    };
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    match head.trim().strip_prefix("ref: refs/heads/") {
+        Some(branch) => Some(format!("{}/{}", repo, branch)),
+        None => Some(repo),
+    }
+}
+
+// .ttd важнее git, git важнее глобальной текущей сессии
+fn current_session(data: &Data, settings: &Settings) -> String {
+    find_session_marker()
+    .map(|(_, name)| name)
+    .or_else(|| settings.git_session.and_then(git_session_name))
+    .or_else(|| data.current_session.clone())
+    .unwrap_or_else(|| "default".to_string())
+}
+
+fn get_data_path() -> Result<PathBuf> {
+    let xdg = BaseDirectories::with_prefix("ttd")?;
+    let path = xdg.place_config_file("tasks.json")?;
+    Ok(path)
+}
+
+fn get_config_path() -> Result<PathBuf> {
+    let xdg = BaseDirectories::with_prefix("ttd")?;
+    let path = xdg.place_config_file("config.toml")?;
+    Ok(path)
+}
 
-        (None, None)
+fn load_config() -> Result<Settings> {
+    let path = get_config_path()?;
+    let (default_threshold, default_strict) = (0.85, false);
+
+    let config: Config = if path.exists() {
+        let toml_str = fs::read_to_string(&path)?;
+        toml::from_str(&toml_str)?
     } else {
-        let mut best_match = None;
-        let mut best_score = 0.0;
-        let mut best_index = None;
+        Config::default()
+    };
 
-        for (i, task) in tasks.iter().enumerate() {
-            let score = jaro_winkler(&task.description.to_lowercase(), &query_lower);
-            if score > best_score && score >= threshold {
-                best_score = score;
-                best_index = Some(i);
-                best_match = Some(task.description.clone());
-            }
-        }
+    let offset_hours = match (&config.app.timezone, config.app.timezone_offset_hours) {
+        (Some(timezone), _) => parse_timezone(timezone)?,
+        (None, Some(hours)) => hours,
+        (None, None) => default_timezone_offset(),
+    };
+
+    Ok(Settings {
+        time: TimeSettings::new(
+            offset_hours,
+            config.app.week_starts,
+            &config.times,
+            holidays::Holidays::load(&config.holidays)?,
+        )?,
+        can_override: config.app.can_override,
+        match_threshold: config.app.exact_match_threshold.unwrap_or(default_threshold),
+        strict_comparison: config.app.strict_comparison.unwrap_or(default_strict),
+        sort_by_priority: config.app.sort_by_priority,
+        git_session: config.app.session_from_git.then_some(config.app.git_session_per_branch),
+        auto_complete_parent: config.app.auto_complete_parent,
+        git_hook_blocks: config.app.git_hook_blocks,
+        theme: config.app.theme,
+        default_command: config.app.default_command,
+        mirror: config.app.mirror.as_deref().map(expand_home),
+        delta_save: config.app.delta_save,
+        storage: storage::Format { pretty: config.app.pretty_json, compress: config.app.compress },
+        templates: config.templates,
+        confirm: config.confirm,
+        local_sessions: config.sessions.iter().filter(|(_, s)| !s.sync).map(|(name, _)| name.clone()).collect(),
+        import_maps: config.import_maps,
+        auto_export: config.auto_export,
+    })
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// Открывает текст во временном файле в $EDITOR и возвращает результат
+fn edit_in_editor(initial: &str) -> Result<String> {
+    let editor = editor_command();
+    let path = std::env::temp_dir().join(format!("ttd-edit-{}.txt", std::process::id()));
+    fs::write(&path, initial)?;
+
+    let status = std::process::Command::new("sh")
+    .arg("-c")
+    .arg(format!("{} \"$1\"", editor))
+    .arg("sh")
+    .arg(&path)
+    .status()
+    .with_context(|| format!("Failed to start editor '{}'", editor))?;
+
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(text?)
+}
+
+fn editor_command() -> String {
+    std::env::var("VISUAL")
+    .or_else(|_| std::env::var("EDITOR"))
+    .unwrap_or_else(|_| "vi".to_string())
+}
+
+// "+42 file" понимают vi, nano, emacs, kak и большинство других редакторов
+fn open_in_editor_at(file: &str, line: usize) -> Result<()> {
+    let editor = editor_command();
+    let program = editor
+    .split_whitespace()
+    .next()
+    .and_then(|p| std::path::Path::new(p).file_name())
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+    let args = match program.as_str() {
+        "code" | "codium" | "code-insiders" => vec!["--goto".to_string(), format!("{}:{}", file, line)],
+        "subl" | "zed" | "hx" => vec![format!("{}:{}", file, line)],
+        _ => vec![format!("+{}", line), file.to_string()],
+    };
+
+    let status = std::process::Command::new("sh")
+    .arg("-c")
+    .arg(format!("{} \"$@\"", editor))
+    .arg("sh")
+    .args(&args)
+    .status()
+    .with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+fn load_data() -> Result<Data> {
+    let path = get_data_path()?;
+    // Читаем потоком, без промежуточной строки на весь файл.
+    // Битый файл не подменяем пустыми данными, иначе следующее сохранение его затрёт
+    let mut data = if path.exists() && fs::metadata(&path)?.len() > 0 {
+        let reader = storage::reader(std::io::BufReader::new(fs::File::open(&path)?))?;
+        profile::time("load", || serde_json::from_reader(reader))
+        .with_context(|| format!("{} is damaged, run 'ttd fsck' to inspect it", path.display()))?
+    } else {
+        Data::default()
+    };
+
+    delta::replay(&mut data, &path)?;
+
+    for tasks in data.sessions.values_mut() {
+        sort_tasks(tasks);
+    }
+    delta::remember_file(&data, &path)?;
+
+    Ok(data)
+}
 
-        if let Some(idx) = best_index {
-            return (Some(idx), Some((best_match.unwrap(), best_score)));
+// Возвращает true, если содержимое файла изменилось
+fn save_data(data: &Data) -> Result<bool> {
+    let path = get_data_path()?;
+    // С delta_save дописываем в журнал только изменившиеся сессии
+    if let Some(changed) = profile::time("delta", || delta::save(data, &path))? {
+        return Ok(changed);
+    }
+    let bytes = profile::time("serialize", || storage::encode(data))?;
+    profile::time("write", || {
+        let changed = fs::read(&path).map_or(true, |old| old != bytes);
+        fs::create_dir_all(path.parent().unwrap())?;
+        // Пишем во временный файл и переименовываем, чтобы не оставить обрезанный JSON
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, &bytes)?;
+        fs::rename(&tmp_path, &path)?;
+        // Журнал уже учтён в полном файле
+        let journal = delta::journal_path(&path);
+        if journal.exists() {
+            fs::remove_file(&journal)?;
         }
+        delta::remember(data, mirror::hash(&bytes))?;
+        Ok(changed)
+    })
+}
+
+fn run_auto_export(config: &AutoExportConfig, data: &Data, current: &str, local: &[String], offset_hours: i64) -> Result<()> {
+    if matches!(config.format, export::Format::Exec) {
+        anyhow::bail!("format 'exec' is not supported for auto_export");
+    }
+    let selection = export::Selection {
+        all_sessions: true,
+        filter: config.filter.as_deref().map(filter::Filter::parse).transpose()?,
+        local: local.to_vec(),
+        ..Default::default()
+    };
+    let tasks = export::select(data, current, &selection);
+    let rendered = export::render(config.format, &tasks, offset_hours)?;
+
+    let path = expand_home(&config.path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, rendered).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+// Короткий id для списка и скриптов: 4 символа base32 (Crockford), дальше длиннее.
+// Младшие 20 бит перемешиваем умножением на нечётное число, чтобы соседние
+// задачи не отличались одной буквой; это биекция, так что id не совпадут
+fn short_id(id: u64) -> String {
+    const ALPHABET: &[u8] = b"0123456789abcdefghjkmnpqrstvwxyz";
+    let mut value = ((id >> 20) << 20) | ((id & 0xFFFFF).wrapping_mul(0x9E3B5) & 0xFFFFF);
+    let mut out = Vec::new();
+    while value > 0 || out.len() < 4 {
+        out.push(ALPHABET[(value % 32) as usize]);
+        value /= 32;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+fn find_by_id(tasks: &[Task], query: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    tasks.iter().position(|t| t.short_id().is_some_and(|id| id == query))
+}
 
-        (None, None)
+fn shown(task: &Task, idx: usize) -> usize {
+    numbering::shown(task, idx)
+}
+
+// Номер из последнего l, даже если порядок с тех пор поменялся
+fn find_by_index(session: &str, tasks: &[Task], index: usize) -> Option<usize> {
+    if let Some(seen) = numbering::resolve(session, tasks, index) {
+        return seen;
+    }
+    if index < tasks.len() { Some(index) } else { None }
+}
+
+fn find_by_name(tasks: &[Task], query: &str, threshold: f64, strict: bool) -> (Option<usize>, Option<(String, f64)>) {
+    let query_lower = query.to_lowercase();
+
+    if let Some(idx) = tasks.iter().position(|t| t.description.to_lowercase() == query_lower) {
+        return (Some(idx), if strict { None } else { Some((tasks[idx].description.clone(), 1.0)) });
+    }
+
+    let mut candidates: Vec<(usize, String, f64)> = tasks.iter().enumerate()
+    .map(|(i, t)| {
+        let score = jaro_winkler(&t.description.to_lowercase(), &query_lower);
+        (i, t.description.clone(), score)
+    })
+    .filter(|(_, _, score)| if strict { *score > threshold } else { *score >= threshold })
+    .collect();
+    candidates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+
+    // Несколько похожих задач: спрашиваем, какую имели в виду. В строгом режиме
+    // (и при проверке дубликатов в a) нечёткое совпадение не принимается вовсе
+    if !strict && candidates.len() > 1 && confirm::is_interactive() {
+        candidates.truncate(9);
+        let options: Vec<String> = candidates.iter().map(|(i, desc, _)| format!("#{} {}", shown(&tasks[*i], *i), desc)).collect();
+        let picked = confirm::choose(&format!("Several tasks match '{}':", query), &options).unwrap_or(None);
+        return match picked {
+            Some(k) => (Some(candidates[k].0), Some((candidates[k].1.clone(), candidates[k].2))),
+            None => (None, None),
+        };
+    }
+
+    match candidates.into_iter().next() {
+        Some((_, desc, score)) if strict => (None, Some((desc, score))),
+        Some((idx, desc, score)) => (Some(idx), Some((desc, score))),
+        None => (None, None),
     }
 }
 
-fn find_task(tasks: &[Task], query: &str, threshold: f64, strict: bool) -> (Option<usize>, Option<(String, f64)>, bool) {
+fn find_task(session: &str, tasks: &[Task], query: &str, threshold: f64, strict: bool) -> (Option<usize>, Option<(String, f64)>, bool) {
+    profile::time("match", || find_task_untimed(session, tasks, query, threshold, strict))
+}
+
+fn find_task_untimed(session: &str, tasks: &[Task], query: &str, threshold: f64, strict: bool) -> (Option<usize>, Option<(String, f64)>, bool) {
+    // "=k3x9" - всегда id; без "=" id проверяем раньше нечёткого поиска, но после индекса
+    // и после точного совпадения названия: задача "todo" не должна уступать id "todo"
+    if let Some(id) = query.strip_prefix('=') {
+        return (find_by_id(tasks, id), None, false);
+    }
+    if !query.chars().all(|c| c.is_ascii_digit()) {
+        let query_lower = query.to_lowercase();
+        if let Some(idx) = tasks.iter().position(|t| t.description.to_lowercase() == query_lower) {
+            return (Some(idx), None, false);
+        }
+        if let Some(idx) = find_by_id(tasks, query) {
+            return (Some(idx), None, false);
+        }
+    }
     if query.chars().all(|c| c.is_ascii_digit()) ||
         (query.starts_with('-') && query[1..].chars().all(|c| c.is_ascii_digit())) {
-            match query.parse::<usize>() {
-                Ok(idx) => (find_by_index(tasks, idx), None, true),
-                Err(_) => (None, None, true)
+            match query.parse::<usize>().ok().and_then(|idx| find_by_index(session, tasks, idx)) {
+                Some(idx) => (Some(idx), None, true),
+                // id тоже бывает из одних цифр ("0123"): пробуем его, если такого номера нет
+                None => match find_by_id(tasks, query) {
+                    Some(idx) => (Some(idx), None, false),
+                    None => (None, None, true),
+                },
             }
         } else {
             let (result, match_info) = find_by_name(tasks, query, threshold, strict);
@@ -400,6 +2029,111 @@ fn find_task(tasks: &[Task], query: &str, threshold: f64, strict: bool) -> (Opti
         }
 }
 
+// Просроченные по expires невыполненные задачи уходят в корзину
+fn expire_tasks(data: &mut Data, offset_hours: i64) -> bool {
+    let now = Utc::now();
+    let mut expired = Vec::new();
+
+    for (session, tasks) in data.sessions.iter_mut() {
+        let mut i = 0;
+        while i < tasks.len() {
+            if tasks[i].is_open() && !tasks[i].locked && tasks[i].expires.is_some_and(|t| t <= now) {
+                let task = tasks.remove(i);
+                let when = format_time(&task.expires, offset_hours);
+                println!("Task '{}' expired {} and was moved to trash", task.description, when);
+                expired.push(TrashEntry {
+                    session: session.clone(),
+                    note: Some(format!("expired {}", when)),
+                    task,
+                    deleted_at: now,
+                });
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    let changed = !expired.is_empty();
+    data.trash.extend(expired);
+    changed
+}
+
+// Когда наступает время follow-up у делегированной задачи, создаём напоминание
+fn create_followups(data: &mut Data) -> bool {
+    let now = Utc::now();
+    let mut changed = false;
+
+    for tasks in data.sessions.values_mut() {
+        let mut reminders = Vec::new();
+        for task in tasks.iter_mut() {
+            if !task.is_open() || task.followup.is_none_or(|t| t > now) {
+                continue;
+            }
+            let delegate = task.delegate.clone().unwrap_or_default();
+            let description = format!("Follow up with {}: {}", delegate, task.description);
+            println!("Follow-up due: added task '{}'", description);
+            reminders.push(Task {
+                description,
+                time: task.followup.take(),
+                created_at: Some(Utc::now()),
+                ..Default::default()
+            });
+        }
+        if !reminders.is_empty() {
+            for reminder in reminders {
+                if !tasks.iter().any(|t| t.description == reminder.description) {
+                    tasks.push(reminder);
+                }
+            }
+            sort_tasks(tasks);
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn print_not_found(query: &str, match_info: Option<(String, f64)>, is_index_search: bool, threshold: f64, strict: bool) {
+    if errors::is_json() {
+        let report = if is_index_search {
+            errors::ErrorReport {
+                suggestion: Some("Run 'l' again to see the current numbers".to_string()),
+                ..errors::ErrorReport::new("index_not_found", format!("Index {} not found", query))
+            }
+        } else if let Some((description, confidence)) = match_info {
+            let (code, suggestion) = if strict {
+                ("no_exact_match", "Set strict_comparison=false in config.toml to enable fuzzy matching")
+            } else {
+                ("low_confidence", "Use a longer query or the task index")
+            };
+            errors::ErrorReport {
+                suggestion: Some(suggestion.to_string()),
+                nearest_match: Some(errors::NearestMatch { description, confidence }),
+                ..errors::ErrorReport::new(code, format!("No task found matching '{}'", query))
+            }
+        } else {
+            errors::ErrorReport::new("not_found", format!("Task '{}' not found", query))
+        };
+        report.emit();
+        return;
+    }
+
+    if is_index_search {
+        println!("Index {} not found, run 'l' again to see the current numbers", query);
+    } else if let Some((matched_desc, score)) = match_info {
+        if strict {
+            println!("No exact match found for '{}'", query);
+            println!("Possible match: \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
+            println!("To enable fuzzy matching, set strict_comparison=false in config.toml");
+        } else {
+            println!("No task found matching \"{}\" with confidence > {:.0}%", query, threshold * 100.0);
+            println!("Closest match was \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
+            println!("To enable strict matching, set strict_comparison=true in config.toml");
+        }
+    } else {
+        println!("Task '{}' not found", query);
+    }
+}
+
 fn format_time(dt: &Option<DateTime<Utc>>, offset_hours: i64) -> String {
     dt.map_or("[  end of times  ]".to_string(), |t| {
         let offset = TimeDelta::hours(offset_hours);
@@ -408,26 +2142,291 @@ fn format_time(dt: &Option<DateTime<Utc>>, offset_hours: i64) -> String {
     })
 }
 
-fn sort_tasks(tasks: &mut Vec<Task>) {
-    tasks.sort_by(|a, b| {
-        match (&a.time, &b.time) {
+// ", turns 36" для годовщины с известным годом
+fn turns_label(rule: &str, at: Option<DateTime<Utc>>, offset_hours: i64) -> String {
+    recurrence::Rule::parse(rule)
+    .ok()
+    .zip(at)
+    .and_then(|(rule, at)| rule.turns(at, offset_hours))
+    .map(|n| format!(", turns {}", n))
+    .unwrap_or_default()
+}
+
+// Точная разница для when: "4d 23h 59m"
+fn format_delta(delta: TimeDelta) -> String {
+    let minutes = (delta + TimeDelta::seconds(30)).num_minutes();
+    let parts = [(minutes / 1440, "d"), (minutes % 1440 / 60, "h"), (minutes % 60, "m")];
+    let text: Vec<String> = parts.iter().filter(|(n, _)| *n > 0).map(|(n, unit)| format!("{}{}", n, unit)).collect();
+    if text.is_empty() { "less than a minute".to_string() } else { text.join(" ") }
+}
+
+// 90 -> "1h30m", 120 -> "2h", 45 -> "45m"
+fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+// Порядок показа: по приоритету, внутри приоритета по времени.
+// Сами задачи не переставляем, чтобы индексы для d/r/t не менялись
+fn display_order(tasks: &[Task], by_priority: bool) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..tasks.len()).collect();
+    if by_priority {
+        order.sort_by_key(|&i| std::cmp::Reverse(tasks[i].priority));
+    }
+    order
+}
+
+// Что делать дальше: открытое, не заблокированное, не отложенное и не ждущее других.
+// Сначала просроченное, потом по приоритету, потом по ближайшему сроку
+fn next_action(tasks: &[Task], contexts: &[String], now: DateTime<Utc>) -> Option<usize> {
+    (0..tasks.len())
+    .filter(|&i| {
+        let t = &tasks[i];
+        t.is_open()
+        && t.effective_status() != Status::Waiting
+        && !t.is_deferred(now)
+        && t.open_blockers(tasks).is_empty()
+        && t.in_contexts(contexts)
+    })
+    .min_by_key(|&i| {
+        let deadline = tasks[i].deadline();
+        (
+            deadline.is_none_or(|d| d >= now),
+            std::cmp::Reverse(tasks[i].priority),
+            deadline.is_none(),
+            deadline,
+        )
+    })
+}
+
+fn sort_tasks(tasks: &mut [Task]) {
+    profile::time("sort", || tasks.sort_by(|a, b| {
+        b.pinned.cmp(&a.pinned).then_with(|| match (&a.time, &b.time) {
             (Some(t1), Some(t2)) => t1.cmp(t2),
                   (Some(_), None) => std::cmp::Ordering::Less,
                   (None, Some(_)) => std::cmp::Ordering::Greater,
                   (None, None) => std::cmp::Ordering::Equal,
-        }
-    });
+        })
+    }));
 }
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let started = std::time::Instant::now();
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if e.use_stderr() && errors::json_requested_in_args() => {
+            errors::from_clap(&e).emit();
+            std::process::exit(2);
+        }
+        Err(e) => e.exit(),
+    };
+    errors::set_format(cli.error_format);
+    confirm::set_interactive(!cli.no_interactive && !cli.porcelain && !errors::is_json() && std::io::stdin().is_terminal());
+    if cli.profile {
+        profile::enable();
+        profile::record("parse args", started.elapsed());
+    }
+
+    match run(cli, started) {
+        Err(e) if errors::is_json() => {
+            errors::from_anyhow(&e).emit();
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+// Задача для помидора по запросу; None - не нашлась или уже закрыта, об этом уже сказано
+fn pomodoro_target(data: &Data, session: &str, query: &str, settings: &Settings) -> Option<(u64, String)> {
+    let sess = data.sessions.get(session).map_or(&[][..], |v| v.as_slice());
+    let (target_idx, match_info, is_index_search) = find_task(session, sess, query, settings.match_threshold, settings.strict_comparison);
+    let Some(idx) = target_idx else {
+        print_not_found(query, match_info, is_index_search, settings.match_threshold, settings.strict_comparison);
+        return None;
+    };
+    if !sess[idx].is_open() {
+        println!("Task #{} '{}' is already {}", shown(&sess[idx], idx), sess[idx].description, sess[idx].status.label());
+        return None;
+    }
+    Some((sess[idx].id?, sess[idx].description.clone()))
+}
+
+fn wait_pomodoro(description: &str, length: &str, offset_hours: i64) -> Result<DateTime<Utc>> {
+    let length = parse_duration(length).with_context(|| format!("Invalid duration '{}', use e.g. 25m", length))?;
+    let start = Utc::now();
+    countdown::run(&format!("Pomodoro: {}", description), start + TimeDelta::from_std(length)?, None, offset_hours)?;
+    countdown::ring("Pomodoro done", description);
+    Ok(start)
+}
+
+fn run(mut cli: Cli, started: std::time::Instant) -> Result<()> {
+    if let Some(Commands::Selftest) = cli.command {
+        return selftest::run();
+    }
+    // Конфиг раньше данных: от него зависит, как их читать и писать
+    let settings = profile::time("config", load_config)?;
+    storage::set(settings.storage);
+    if settings.delta_save {
+        delta::enable();
+    }
+    if let Some(Commands::Fsck { repair }) = cli.command {
+        return fsck::run(&get_data_path()?, repair);
+    }
+    theme::set(settings.theme);
+    // Помидор идёт долго: отсчитываем его до загрузки данных, а отмечаем на свежей копии.
+    // Иначе сохранение в конце затёрло бы всё, что за это время поменяли из других терминалов
+    if let Some(Commands::Pomo { query, length, waited }) = &mut cli.command {
+        let mut data = load_data()?;
+        data.assign_ids();
+        let session = cli.use_session.clone().unwrap_or_else(|| current_session(&data, &settings));
+        let Some((id, description)) = pomodoro_target(&data, &session, query, &settings) else {
+            return Ok(());
+        };
+        let start = wait_pomodoro(&description, length, settings.time.offset_hours)?;
+        *waited = Some((id, start, description));
+    }
     let mut data = load_data()?;
-    let (offset_hours, can_override, match_threshold, strict_comparison) = load_config()?;
+    // id до снимка истории: иначе старые задачи без id попадут в неё как созданные
+    data.assign_ids();
+    let history_before = history::Snapshot::take(&data);
+    let offset_hours = settings.time.offset_hours;
+
+    if let Some(path) = &settings.mirror {
+        profile::time("mirror", || mirror::sync_in(&mut data, path, &settings.local_sessions, offset_hours))?;
+    }
+
+    let quiet = cli.porcelain || matches!(cli.command, Some(Commands::Nx | Commands::Tags { complete: Some(_) }));
+    let (vacation_changed, followups_changed, expired_changed, upgraded) = profile::time("housekeeping", || {
+        Ok::<_, anyhow::Error>((
+            vacation::check_return(&mut data, offset_hours)?,
+            create_followups(&mut data),
+            expire_tasks(&mut data, offset_hours),
+            upgrade::check(&mut data, &get_config_path()?, quiet)?,
+        ))
+    })?;
+    if vacation_changed || followups_changed || expired_changed || upgraded {
+        save_data(&data)?;
+    }
+
+    let use_session = cli.use_session.clone();
+    // Без команды: сводка, l или od report, глобальные флаги при этом сохраняются
+    let cli = match (cli.command.is_some(), settings.default_command) {
+        (true, _) => cli,
+        (false, DefaultCommand::None) => return Ok(()),
+        (false, DefaultCommand::Summary) => {
+            let name = use_session.unwrap_or_else(|| current_session(&data, &settings));
+            let tasks = data.sessions.get(&name).map_or(&[][..], |v| v.as_slice());
+            report::summary(&name, tasks, offset_hours);
+            return Ok(());
+        }
+        (false, DefaultCommand::L) => Cli::parse_from(std::env::args().chain(["l".to_string()])),
+        (false, DefaultCommand::Due) => Cli::parse_from(std::env::args().chain(["od".to_string(), "report".to_string()])),
+    };
+    // Снимок до команды для undo; сами undo и redo в журнал не пишутся
+    data.assign_ids();
+    let undoable = !matches!(cli.command, Some(Commands::Undo { .. } | Commands::Redo));
+    let before = if undoable { Some(undo::snapshot(&data)?) } else { None };
+    profile::time("command", || run_command(cli, &mut data, &settings))?;
+    data.assign_ids();
+    if let Some(before) = before {
+        let command_line = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+        undo::record(&command_line, before, &data)?;
+    }
+    history::record(&history_before, &data, offset_hours)?;
 
-    let current_session_name = data.current_session.clone().unwrap_or_else(|| "default".to_string());
+    let mirror_text = settings.mirror.as_ref().map(|_| mirror::prepare(&mut data, &settings.local_sessions, offset_hours));
+    let changed = save_data(&data)?;
+    numbering::flush()?;
+    if let (Some(path), Some(text)) = (&settings.mirror, mirror_text) {
+        mirror::write(path, &text)?;
+    }
+
+    if let Some(auto_export) = &settings.auto_export {
+        if changed || auto_export.on == AutoExportTrigger::Always {
+            let current_session_name = use_session.unwrap_or_else(|| current_session(&data, &settings));
+            let result = profile::time("auto-export", || run_auto_export(auto_export, &data, &current_session_name, &settings.local_sessions, offset_hours));
+            if let Err(e) = result {
+                eprintln!("Warning: auto-export failed: {}", e);
+            }
+        }
+    }
+
+    let file_size = get_data_path().ok().and_then(|p| fs::metadata(p).ok()).map_or(0, |m| m.len());
+    profile::report(&data, file_size, started.elapsed());
+    Ok(())
+}
+
+// Разбивает строку на аргументы как shell: кавычки '...' и "...", экранирование \\
+fn split_command_line(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                current.push(chars.next().ok_or_else(|| anyhow::anyhow!("Trailing backslash"))?);
+                in_word = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        anyhow::bail!("Unclosed quote");
+    }
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+// Выполняет одну команду над данными в памяти; сохранение делает вызывающий
+fn run_command(cli: Cli, data: &mut Data, settings: &Settings) -> Result<()> {
+    let offset_hours = settings.time.offset_hours;
+    let can_override = settings.can_override;
+    let match_threshold = settings.match_threshold;
+    let strict_comparison = settings.strict_comparison;
 
-    match cli.command {
+    // -s работает только для этого запуска и не меняет текущую сессию
+    let current_session_name = cli.use_session.clone().unwrap_or_else(|| current_session(data, settings));
+    numbering::set_session(&current_session_name);
+    data.assign_ids();
+
+    let Some(command) = cli.command else {
+        anyhow::bail!("No command given");
+    };
+    match command {
         Commands::Ss => {
+            if cli.porcelain {
+                porcelain::print_header();
+                let mut names: Vec<_> = data.sessions.keys().collect();
+                names.sort();
+                for name in names {
+                    porcelain::print_session(name, &data.sessions[name], *name == current_session_name);
+                }
+                return Ok(());
+            }
             if data.sessions.is_empty() {
                 println!("No sessions available");
                 return Ok(());
@@ -439,14 +2438,19 @@ fn main() -> Result<()> {
             .max()
             .unwrap_or(0);
 
-            let mut stdout = StandardStream::stdout(ColorChoice::Always);
+            let mut stdout = StandardStream::stdout(theme::color_choice());
 
             // Выводим список сессий
             for (session_name, tasks) in &data.sessions {
-                let completed = tasks.iter().filter(|t| t.done).count();
+                if cli.plain {
+                    let is_current = *session_name == current_session_name;
+                    print_plain_session_header(session_name, tasks, is_current, &[]);
+                    continue;
+                }
+                let completed = tasks.iter().filter(|t| !t.is_open()).count();
                 let total = tasks.len();
 
-                let is_current = Some(session_name.as_str()) == data.current_session.as_deref();
+                let is_current = *session_name == current_session_name;
                 let color = if is_current { Color::Green } else { Color::White };
 
                 let status_text = format!(" ({}/{})", completed, total);
@@ -463,24 +2467,62 @@ fn main() -> Result<()> {
             }
         },
         Commands::S { session } => {
-            if let Some(session_name) = session {
-                data.sessions.entry(session_name.clone()).or_insert_with(Vec::new);
+            if let Some(mut session_name) = session {
+                let switched_from = data.current_session.clone().unwrap_or_else(|| "default".to_string());
+                // "s -" как "cd -": назад к предыдущей сессии
+                if session_name == "-" {
+                    session_name = data.previous_session.clone().context("No previous session to switch back to")?;
+                }
+                if session_name != switched_from {
+                    data.previous_session = Some(switched_from);
+                }
+                data.sessions.entry(session_name.clone()).or_default();
                 data.current_session = Some(session_name.clone());
                 println!("Switched to session '{}'", session_name);
+                if let Some((path, marker)) = find_session_marker() {
+                    println!("Note: {} keeps this directory on session '{}'", path.display(), marker);
+                } else if let Some(name) = settings.git_session.and_then(git_session_name) {
+                    println!("Note: inside this git repository the session is '{}' (session_from_git = true)", name);
+                }
+            } else if let Some((path, marker)) = find_session_marker() {
+                println!("Current session: '{}' (from {})", marker, path.display());
+            } else if let Some(name) = settings.git_session.and_then(git_session_name) {
+                println!("Current session: '{}' (from git, session_from_git = true)", name);
+            } else if let Some(current_sess) = &data.current_session {
+                println!("Current session: '{}'", current_sess);
             } else {
-                if let Some(current_sess) = &data.current_session {
-                    println!("Current session: '{}'", current_sess);
-                } else {
-                    println!("No current session (using 'default')");
-                }
+                println!("No current session (using 'default')");
             }
         },
-        Commands::A { parts } => {
+        Commands::A { parts, details, template } => {
+            let (parts, priority) = match &template {
+                Some(name) => {
+                    let template = template::find(name, &data.templates, &settings.templates)?;
+                    (template.expand(&parts), template.priority)
+                }
+                None => (parts, None),
+            };
             if parts.is_empty() {
-                println!("Usage: a <task> [in|at] <time>");
+                println!("Usage: a <task> [in|at <time> [for <duration>]] [every <rule>] [expires in|at <time>]");
+                return Ok(());
+            }
+            let parts = split_inline_time(parts, &settings.time);
+            // Теги и приоритет можно писать в описании или отдельными словами: a 'fix bug +work !high'
+            let mut tags = Vec::new();
+            let mut priority = priority;
+            let is_marker = |p: &String| as_tag(p).is_some() || as_priority(p).is_some();
+            let task_desc = split_markers(&parts[0], &mut tags, &mut priority);
+            for part in parts[1..].iter().filter(|p| is_marker(p)) {
+                split_markers(part, &mut tags, &mut priority);
+            }
+            let parts: Vec<String> = std::iter::once(task_desc.clone())
+            .chain(parts[1..].iter().filter(|p| !is_marker(p)).cloned())
+            .collect();
+
+            if task_desc.is_empty() {
+                println!("Task needs a description, not only tags");
                 return Ok(());
             }
-            let task_desc = parts[0].clone();
 
             if task_desc.chars().all(|c| c.is_ascii_digit()) {
                 println!("Task name '{}' looks like index. Use letters!", task_desc);
@@ -488,38 +2530,101 @@ fn main() -> Result<()> {
                 return Ok(());
             }
 
-            let time = if parts.len() > 2 {
-                let prefix = &parts[1];
-                let time_str = &parts[2];
+            // a <task> [in|at <time> [for <duration>]] [every <rule>] [expires in|at <time>]
+            let keyword_at = |keyword: &str| parts.iter().skip(1).position(|p| p == keyword).map(|i| i + 1);
+            let (every_pos, expires_pos) = (keyword_at("every"), keyword_at("expires"));
+            let segment = |start: usize| {
+                let end = [every_pos, expires_pos].into_iter().flatten().filter(|&p| p >= start).min().unwrap_or(parts.len());
+                &parts[start..end]
+            };
+            let time_parts = segment(1);
+            let expiry_parts = expires_pos.map_or(&[][..], |p| segment(p + 1));
 
-                if prefix == "in" {
-                    Some(parse_relative_time(time_str)?)
-                } else if prefix == "at" {
-                    Some(parse_absolute_time(time_str, offset_hours)?)
-                } else {
-                    println!("Unknown time prefix '{}'. Use 'in' for relative time or 'at' for absolute time.", prefix);
+            let (recurrence, rule) = match every_pos.map(|p| segment(p + 1).join(" ")) {
+                Some(rule) if rule.is_empty() => {
+                    println!("Usage: a <task> [in|at <time> [for <duration>]] [every <rule>] [expires in|at <time>]");
                     return Ok(());
                 }
+                Some(rule) => {
+                    let parsed = recurrence::Rule::parse(&rule)?;
+                    (Some(rule), Some(parsed))
+                }
+                None => (None, None),
+            };
+
+            let (time, end) = if time_parts.len() > 1 {
+                match parse_time_range(time_parts, &settings.time)? {
+                    Some((time, end)) => (Some(time), end),
+                    None => return Ok(()),
+                }
+            } else {
+                // "every 0 9 * * MON-FRI" без срока начинается с ближайшего совпадения
+                (rule.as_ref().and_then(|r| r.first(Utc::now(), settings.time.offset_hours)), None)
+            };
+            let recurrence = match rule.and_then(|r| r.anchored(time.unwrap_or_else(Utc::now), settings.time.offset_hours)) {
+                Some(anchored) => Some(anchored),
+                None => recurrence,
+            };
+
+            let expires = if expiry_parts.len() > 1 {
+                match parse_prefixed_time(&expiry_parts[0], &expiry_parts[1..].join(" "), &settings.time)? {
+                    Some(time) => Some(time),
+                    None => return Ok(()),
+                }
+            } else if expires_pos.is_some() {
+                println!("Usage: a <task> [in|at <time> [for <duration>]] [every <rule>] [expires in|at <time>]");
+                return Ok(());
             } else {
                 None
             };
 
-            let sess = data.sessions.entry(current_session_name.clone()).or_insert_with(Vec::new);
-            let (exact_match_idx, match_info, _) = find_task(sess, &task_desc, match_threshold, true);
-            let exact_description_match = sess.iter().position(|t| t.description == task_desc);
+            let details = match details.as_deref() {
+                None => None,
+                Some("-") => {
+                    let mut input = String::new();
+                    std::io::stdin().read_to_string(&mut input)?;
+                    Some(input)
+                }
+                Some("") => Some(edit_in_editor("")?),
+                Some(text) => Some(text.to_string()),
+            }
+            .map(|d| d.trim_end().to_string())
+            .filter(|d| !d.is_empty());
+
+            let sess = data.sessions.entry(current_session_name.clone()).or_default();
+            // Дубликат ищем только по названию: новое название может совпасть с чужим id или индексом
+            let (exact_match_idx, match_info) = find_by_name(sess, &task_desc, match_threshold, true);
+            // Регистр не важен, как и в find_by_name: "buy milk" - та же задача, что "Buy milk"
+            let task_desc_lower = task_desc.to_lowercase();
+            let exact_description_match = sess.iter().position(|t| t.description.to_lowercase() == task_desc_lower);
 
-            if let Some(idx) = exact_description_match {
+            if let Some(idx) = exact_description_match.filter(|&i| sess[i].locked) {
+                print_locked(idx, &sess[idx]);
+                return Ok(());
+            } else if let Some(idx) = exact_description_match {
                 if can_override {
                     sess[idx].time = time;
-                    sess[idx].done = false;
+                    sess[idx].end = end;
+                    sess[idx].status = Status::Todo;
+                    sess[idx].completed_at = None;
+                    if details.is_some() {
+                        sess[idx].details = details;
+                    }
+                    sess[idx].expires = expires;
+                    sess[idx].recurrence = recurrence;
+                    if !tags.is_empty() {
+                        sess[idx].tags = tags;
+                    }
+                    if priority.is_some() {
+                        sess[idx].priority = priority;
+                    }
                     println!("Overrode existing task '{}'", task_desc);
                 } else {
                     println!("Task '{}' already exists", task_desc);
                     println!("Set can_override=true in ~/.config/ttd/config.toml to override");
                     return Ok(());
                 }
-            } else if let Some(idx) = exact_match_idx {
-                let (matched_desc, score) = match_info.unwrap();
+            } else if let (Some(idx), Some((matched_desc, score))) = (exact_match_idx, match_info) {
                 if strict_comparison {
                     println!("No exact match found for '{}'", task_desc);
                     println!("Possible match: \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
@@ -530,10 +2635,27 @@ fn main() -> Result<()> {
                     println!("To enable strict matching, set strict_comparison=true in config.toml");
                 }
 
+                if sess[idx].locked {
+                    print_locked(idx, &sess[idx]);
+                    return Ok(());
+                }
                 if can_override {
                     println!("Overriding due to can_override=true");
                     sess[idx].time = time;
-                    sess[idx].done = false;
+                    sess[idx].end = end;
+                    sess[idx].status = Status::Todo;
+                    sess[idx].completed_at = None;
+                    if details.is_some() {
+                        sess[idx].details = details;
+                    }
+                    sess[idx].expires = expires;
+                    sess[idx].recurrence = recurrence;
+                    if !tags.is_empty() {
+                        sess[idx].tags = tags;
+                    }
+                    if priority.is_some() {
+                        sess[idx].priority = priority;
+                    }
                 } else {
                     println!("Set can_override=true to override or use different name");
                     return Ok(());
@@ -542,15 +2664,132 @@ fn main() -> Result<()> {
                 let task = Task {
                     description: task_desc.clone(),
                     time,
-                    done: false,
+                    end,
+                    details,
+                    expires,
+                    recurrence,
+                    tags,
+                    priority,
+                    created_at: Some(Utc::now()),
+                    ..Default::default()
                 };
                 sess.push(task);
                 println!("Added new task '{}'", task_desc);
             }
+            warn_if_holiday(time, &settings.time);
             sort_tasks(sess);
         },
+        Commands::Sub { parent, parts } => {
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &parent, match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(&parent, match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            if sess[idx].locked {
+                print_locked(idx, &sess[idx]);
+                return Ok(());
+            }
+
+            // sub <parent> <task> [in|at <time>]
+            let time = if parts.len() > 2 {
+                match parse_prefixed_time(&parts[1], &parts[2..].join(" "), &settings.time)? {
+                    Some(time) => Some(time),
+                    None => return Ok(()),
+                }
+            } else {
+                None
+            };
+
+            let parent = &mut sess[idx];
+            parent.subtasks.push(Task {
+                description: parts[0].clone(),
+                time,
+                created_at: Some(Utc::now()),
+                ..Default::default()
+            });
+            println!("Added subtask #{}.{} '{}' to '{}'", shown(parent, idx), parent.subtasks.len() - 1, parts[0], parent.description);
+            if parent.status == Status::Done && settings.auto_complete_parent {
+                parent.status = Status::Todo;
+                parent.completed_at = None;
+                println!("Reopened '{}'", parent.description);
+            }
+        },
+        Commands::N { query, show, clear } => {
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            if !show && sess[idx].locked {
+                print_locked(idx, &sess[idx]);
+                return Ok(());
+            }
+            let task = &mut sess[idx];
+
+            // Заметка хранится в details, тот же текст, что и у 'a --details'
+            if show {
+                match &task.details {
+                    Some(note) => println!("{}", note),
+                    None => println!("Task #{} '{}' has no note", shown(task, idx), task.description),
+                }
+            } else if clear {
+                task.details = None;
+                println!("Removed note from #{} '{}'", shown(task, idx), task.description);
+            } else {
+                let note = edit_in_editor(task.details.as_deref().unwrap_or(""))?;
+                let note = note.trim_end();
+                task.details = (!note.is_empty()).then(|| note.to_string());
+                println!(
+                    "{} note of #{} '{}'",
+                    if task.details.is_some() { "Saved" } else { "Removed" },
+                    shown(task, idx),
+                    task.description
+                );
+            }
+        },
+        Commands::Dep { parts } => {
+            // dep <task> on|off <blocker> [...]
+            if parts.len() < 3 || !matches!(parts[1].as_str(), "on" | "off") {
+                println!("Usage: dep <task> on|off <blocker> [...]");
+                return Ok(());
+            }
+            let adding = parts[1] == "on";
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &parts[0], match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(&parts[0], match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            if sess[idx].locked {
+                print_locked(idx, &sess[idx]);
+                return Ok(());
+            }
+
+            for query in &parts[2..] {
+                let (blocker_idx, match_info, is_index_search) = find_task(&current_session_name, sess, query, match_threshold, strict_comparison);
+                let Some(blocker_idx) = blocker_idx else {
+                    print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison);
+                    continue;
+                };
+                let blocker_id = sess[blocker_idx].id.context("Task has no id")?;
+                let (task_desc, blocker_desc) = (sess[idx].description.clone(), sess[blocker_idx].description.clone());
+                let (number, blocker_number) = (shown(&sess[idx], idx), shown(&sess[blocker_idx], blocker_idx));
+
+                if !adding {
+                    sess[idx].blocked_by.retain(|&id| id != blocker_id);
+                    println!("#{} '{}' no longer depends on #{} '{}'", number, task_desc, blocker_number, blocker_desc);
+                } else if blocker_idx == idx || depends_on(sess, blocker_id, sess[idx].id.context("Task has no id")?) {
+                    println!("Cannot make #{} '{}' depend on #{} '{}': that would be a cycle", number, task_desc, blocker_number, blocker_desc);
+                } else if !sess[idx].blocked_by.contains(&blocker_id) {
+                    sess[idx].blocked_by.push(blocker_id);
+                    println!("#{} '{}' is now blocked by #{} '{}'", number, task_desc, blocker_number, blocker_desc);
+                }
+            }
+        },
         Commands::R { ref parts } => {
-            handle_remove(parts, &mut data, &current_session_name, match_threshold, strict_comparison)?;
+            handle_remove(parts, data, &current_session_name, settings)?;
         },
         Commands::Rs { parts } => {
             if parts.is_empty() {
@@ -564,209 +2803,1370 @@ fn main() -> Result<()> {
                     continue;
                 }
 
-                if session == "default" {
-                    println!("Cannot remove default session");
-                    continue;
+                if session == "default" {
+                    println!("Cannot remove default session");
+                    continue;
+                }
+
+                if !data.sessions.contains_key(&session) {
+                    println!("Session '{}' not found", session);
+                    continue;
+                }
+
+                let tasks = data.sessions.get(&session).unwrap();
+                let uncompleted_count = tasks.iter().filter(|t| t.is_open()).count();
+                let total_count = tasks.len();
+
+                if total_count > 0 && settings.confirm.remove_session {
+                    println!("Session '{}' contains {} tasks ({} uncompleted)",
+                             session, total_count, uncompleted_count);
+
+                    let question = if uncompleted_count > 0 {
+                        "Are you sure you want to delete this session with uncompleted tasks?"
+                    } else {
+                        "Session contains only completed tasks. Delete anyway?"
+                    };
+                    if !confirm::ask(question)? {
+                        println!("Session deletion cancelled for '{}'", session);
+                        continue;
+                    }
+                }
+
+                // Удаляем сессию, задачи уходят в корзину
+                let now = Utc::now();
+                for task in data.sessions.remove(&session).unwrap_or_default() {
+                    data.trash.push(TrashEntry {
+                        session: session.clone(),
+                        note: Some("session removed".to_string()),
+                        task,
+                        deleted_at: now,
+                    });
+                }
+
+                // Если удаляемая сессия была текущей - переключаемся на default
+                if Some(&session) == data.current_session.as_ref() {
+                    data.current_session = Some("default".to_string());
+                    println!("Switched to default session");
+                }
+                if Some(&session) == data.previous_session.as_ref() {
+                    data.previous_session = None;
+                }
+
+                println!("Session '{}' deleted successfully", session);
+            }
+        },
+        Commands::D { ref parts, force } => {
+            handle_done(parts, data, &current_session_name, settings, Status::Done, force, false)?;
+        },
+        Commands::Pin { ref parts } => {
+            handle_pin(parts, data, &current_session_name, settings, true)?;
+        },
+        Commands::Unpin { ref parts } => {
+            handle_pin(parts, data, &current_session_name, settings, false)?;
+        },
+        Commands::Tags { complete: Some(prefix) } => tags::print_completions(data, &prefix),
+        Commands::Tags { complete: None } => tags::print_list(data),
+        Commands::Tg { command: TagCommand::Rename { from, to } } => {
+            let (from, to) = (tags::parse(&from)?, tags::parse(&to)?);
+            let taken = data.sessions.values().flatten().any(|t| t.tags.iter().any(|tag| tag.eq_ignore_ascii_case(&to)));
+            if taken && !from.eq_ignore_ascii_case(&to) {
+                println!("Tag #{} is already in use, 'tag merge +{} +{}' joins the two", to, from, to);
+                return Ok(());
+            }
+            let count = tags::rename(data, std::slice::from_ref(&from), &to)?;
+            println!("Renamed #{} to #{} on {} tasks", from, to, count);
+        },
+        // tag merge +home +flat +house: все, кроме последнего, сливаются в последний
+        Commands::Tg { command: TagCommand::Merge { tags } } => {
+            let tags = tags.iter().map(|t| tags::parse(t)).collect::<Result<Vec<_>>>()?;
+            let (into, from) = tags.split_last().unwrap();
+            let count = tags::rename(data, from, into)?;
+            println!("Merged {} into #{} on {} tasks", tags_label(from), into, count);
+        },
+        Commands::Tg { command: TagCommand::Add { tag, parts, filter } } => {
+            handle_tag(&tag, &parts, filter.as_deref(), data, &current_session_name, settings, true)?;
+        },
+        Commands::Tg { command: TagCommand::Rm { tag, parts, filter } } => {
+            handle_tag(&tag, &parts, filter.as_deref(), data, &current_session_name, settings, false)?;
+        },
+        Commands::Lock { ref parts } => {
+            handle_lock(parts, data, &current_session_name, settings, true)?;
+        },
+        Commands::Unlock { ref parts } => {
+            handle_lock(parts, data, &current_session_name, settings, false)?;
+        },
+        Commands::Ud { ref parts } => {
+            handle_done(parts, data, &current_session_name, settings, Status::Todo, false, false)?;
+        },
+        Commands::St { ref parts } => {
+            handle_done(parts, data, &current_session_name, settings, Status::InProgress, false, false)?;
+        },
+        Commands::Wt { ref parts } => {
+            handle_done(parts, data, &current_session_name, settings, Status::Waiting, false, false)?;
+        },
+        Commands::Cn { ref parts } => {
+            handle_done(parts, data, &current_session_name, settings, Status::Cancelled, false, false)?;
+        },
+        Commands::Sk { ref parts } => {
+            handle_done(parts, data, &current_session_name, settings, Status::Done, false, true)?;
+        },
+        Commands::T { ref parts } => {
+            if parts.is_empty() {
+                println!("Usage: t <index|task_name> [...] [in|at <time> [for <duration>] | from <time> to <time>]");
+                return Ok(());
+            }
+            // Задачи - всё до in/at/from, дальше новое время; без него время снимается
+            let split = parts.iter().skip(1).position(|p| matches!(p.as_str(), "in" | "at" | "from")).map_or(parts.len(), |i| i + 1);
+            let range = if split < parts.len() {
+                match parse_time_range(&parts[split..], &settings.time)? {
+                    Some(range) => Some(range),
+                    None => return Ok(()),
+                }
+            } else {
+                None
+            };
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let queries = expand_ranges(sess, &parts[..split])?;
+
+            // Сначала находим все задачи: после сортировки индексы поменяются
+            let mut targets = Vec::new();
+            for query in &queries {
+                match find_task(&current_session_name, sess, query, match_threshold, strict_comparison) {
+                    (Some(idx), _, _) => targets.push(idx),
+                    (None, match_info, is_index_search) => print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison),
+                }
+            }
+            targets.sort_unstable();
+            targets.dedup();
+            if settings.confirm.is_bulk(targets.len()) && !confirm::ask(&format!("Change time for {} tasks?", targets.len()))? {
+                println!("Nothing changed");
+                return Ok(());
+            }
+
+            for idx in targets {
+                if sess[idx].locked {
+                    print_locked(idx, &sess[idx]);
+                    continue;
+                }
+                let old_time = format_range(&sess[idx], offset_hours);
+                match range {
+                    // Без нового конца событие переезжает целиком
+                    Some((time, None)) => sess[idx].move_to(Some(time)),
+                    Some((time, end)) => {
+                        sess[idx].time = Some(time);
+                        sess[idx].end = end;
+                    }
+                    None => sess[idx].move_to(None),
+                }
+                let new_time = format_range(&sess[idx], offset_hours);
+                println!("Changed time for '{}': {} -> {}", sess[idx].description, old_time, new_time);
+                warn_if_holiday(sess[idx].time, &settings.time);
+            }
+            sort_tasks(sess);
+        }
+        Commands::E { parts } => {
+            let Some((query, words)) = parts.split_first() else {
+                println!("Usage: e <index|task_name> [new description]");
+                return Ok(());
+            };
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, query, match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            if sess[idx].locked {
+                print_locked(idx, &sess[idx]);
+                return Ok(());
+            }
+            // Без нового текста правим старый в $EDITOR
+            let text = if words.is_empty() {
+                edit_in_editor(&sess[idx].description)?
+            } else {
+                words.join(" ")
+            };
+            // Теги и приоритет в новом тексте работают как в add
+            let mut tags = sess[idx].tags.clone();
+            let mut priority = sess[idx].priority;
+            let description = split_markers(text.trim(), &mut tags, &mut priority);
+            if description.is_empty() {
+                println!("Task needs a description, not only tags");
+                return Ok(());
+            }
+            if description.chars().all(|c| c.is_ascii_digit()) {
+                println!("Task name '{}' looks like index. Use letters!", description);
+                return Ok(());
+            }
+            if sess.iter().enumerate().any(|(i, t)| i != idx && t.description == description) {
+                println!("Task '{}' already exists", description);
+                return Ok(());
+            }
+            let task = &mut sess[idx];
+            if task.description == description && task.tags == tags && task.priority == priority {
+                println!("Nothing changed");
+                return Ok(());
+            }
+            if task.description == description {
+                println!("Updated #{} '{}'", shown(task, idx), description);
+            } else {
+                println!("Renamed #{} '{}' -> '{}'", shown(task, idx), task.description, description);
+            }
+            task.description = description;
+            task.tags = tags;
+            task.priority = priority;
+            sort_tasks(sess);
+        },
+        Commands::Dup { parts, to } => {
+            if parts.is_empty() || parts.len() == 2 {
+                println!("Usage: dup <index|task_name> [in|at <time> [for <duration>]] [--to <session>]");
+                return Ok(());
+            }
+            let sess = data.sessions.get(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &parts[0], match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(&parts[0], match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            let mut copy = sess[idx].duplicate();
+            let number = shown(&sess[idx], idx);
+            if parts.len() > 2 {
+                match parse_time_range(&parts[1..], &settings.time)? {
+                    Some((time, None)) => copy.move_to(Some(time)),
+                    Some((time, end)) => {
+                        copy.time = Some(time);
+                        copy.end = end;
+                    }
+                    None => return Ok(()),
+                }
+            }
+            let target = to.unwrap_or_else(|| current_session_name.clone());
+            println!("Copied #{} '{}' to session '{}' {}", number, copy.description, target, format_range(&copy, offset_hours));
+            warn_if_holiday(copy.time, &settings.time);
+            let tasks = data.sessions.entry(target).or_default();
+            tasks.push(copy);
+            sort_tasks(tasks);
+        },
+        Commands::Mv { query, session, copy } => {
+            if session == current_session_name {
+                println!("Task is already in session '{}'", session);
+                return Ok(());
+            }
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            if !copy && sess[idx].locked {
+                print_locked(idx, &sess[idx]);
+                return Ok(());
+            }
+            let description = sess[idx].description.clone();
+            if data.sessions.get(&session).is_some_and(|tasks| tasks.iter().any(|t| t.description == description)) {
+                println!("Task '{}' already exists in session '{}'", description, session);
+                return Ok(());
+            }
+            // Копия получает новый id, перенос сохраняет задачу целиком
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let number = shown(&sess[idx], idx);
+            let task = if copy { sess[idx].duplicate() } else { sess.remove(idx) };
+            let created = !data.sessions.contains_key(&session);
+            println!(
+                "{} #{} '{}' to {}session '{}'",
+                if copy { "Copied" } else { "Moved" },
+                number,
+                description,
+                if created { "new " } else { "" },
+                session
+            );
+            let tasks = data.sessions.entry(session).or_default();
+            tasks.push(task);
+            sort_tasks(tasks);
+        },
+        Commands::Sn { ref parts, overdue } => {
+            let usage = "Usage: snooze <index|task_name> <delta> or snooze --overdue <delta>";
+            let (query, delta) = match parts.split_first() {
+                _ if overdue => (None, parts.join(" ")),
+                Some((query, delta)) if !delta.is_empty() => (Some(query), delta.join(" ")),
+                _ => {
+                    println!("{}", usage);
+                    return Ok(());
+                }
+            };
+            // Тот же разбор, что у "in 2h", но сдвиг от текущего срока задачи
+            let delta = parse_span(&delta)?;
+            if delta <= TimeDelta::zero() {
+                println!("{}", usage);
+                return Ok(());
+            }
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let now = Utc::now();
+
+            match query {
+                Some(query) => {
+                    let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, query, match_threshold, strict_comparison);
+                    let Some(idx) = target_idx else {
+                        print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison);
+                        return Ok(());
+                    };
+                    if sess[idx].locked {
+                        print_locked(idx, &sess[idx]);
+                        return Ok(());
+                    }
+                    let old_time = format_range(&sess[idx], offset_hours);
+                    let new_start = sess[idx].time.unwrap_or(now) + delta;
+                    sess[idx].move_to(Some(new_start));
+                    println!("Snoozed '{}': {} -> {}", sess[idx].description, old_time, format_range(&sess[idx], offset_hours));
+                    warn_if_holiday(sess[idx].time, &settings.time);
+                }
+                None => {
+                    let mut count = 0;
+                    for task in sess.iter_mut().filter(|t| t.is_open() && !t.locked && t.deadline().is_some_and(|d| d < now)) {
+                        let old_time = format_range(task, offset_hours);
+                        task.move_to(task.time.map(|t| t + delta));
+                        println!("Snoozed '{}': {} -> {}", task.description, old_time, format_range(task, offset_hours));
+                        count += 1;
+                    }
+                    if count == 0 {
+                        println!("Nothing is overdue in session '{}'", current_session_name);
+                    }
+                }
+            }
+            sort_tasks(sess);
+        },
+        Commands::L { near, easy, big, speak, by_priority, tag, long, all, filter } => {
+            let filter = filter.to_filter(offset_hours)?;
+            // --all показывает задачи вне активного контекста
+            let contexts: &[String] = if all { &[] } else { &data.active_contexts };
+            let options = ListOptions {
+                offset_hours,
+                vacation: data.vacation.as_ref(),
+                holidays: &settings.time.holidays,
+                big,
+                plain: cli.plain,
+                show_scheduled: data.sessions.get(&current_session_name)
+                .is_some_and(|tasks| tasks.iter().any(|t| t.scheduled.is_some())),
+                time_width: data.sessions.get(&current_session_name)
+                .map_or(0, |tasks| tasks.iter().map(|t| format_range(t, offset_hours).len()).max().unwrap_or(0)),
+                tags_column: None,
+                long,
+                session: data.sessions.get(&current_session_name).map_or(&[][..], |v| v.as_slice()),
+            };
+            let mut spoken = Vec::new();
+            let now = Utc::now();
+
+            let color = Color::Green;
+
+            let sess_slice = data.sessions.get(&current_session_name).map_or(&[][..], |v| v.as_slice());
+            let shown = |t: &Task| {
+                (all || !t.is_deferred(now))
+                && t.in_contexts(contexts)
+                && near.as_deref().is_none_or(|place| t.is_near(place, match_threshold))
+                && (!easy || t.difficulty.is_some_and(|d| d <= EASY_DIFFICULTY))
+                && t.has_tags(&tag)
+                && filter.matches(t, now)
+            };
+            numbering::remember(&current_session_name, sess_slice)?;
+
+            if cli.porcelain {
+                porcelain::print_header();
+                porcelain::print_session(&current_session_name, sess_slice, true);
+                for i in display_order(sess_slice, by_priority || settings.sort_by_priority) {
+                    if !shown(&sess_slice[i]) {
+                        continue;
+                    }
+                    porcelain::print_task(&current_session_name, i, &sess_slice[i]);
+                }
+                return Ok(());
+            }
+
+            let completed = sess_slice.iter().filter(|t| !t.is_open()).count();
+            let total = sess_slice.len();
+
+            let status_text = format!(" ({}/{})", completed, total);
+            let marker = ">";
+
+            if cli.plain {
+                print_plain_session_header(&current_session_name, sess_slice, true, contexts);
+            } else {
+                let mut stdout = StandardStream::stdout(theme::color_choice());
+
+                // Выводим сессию с цветовым выделением
+                stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?; // Выделяем текущую жирным
+                write!(stdout, "{} {}", marker, &current_session_name)?;
+                stdout.reset()?;
+
+                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
+                write!(stdout, "{}", status_text)?;
+                print_context_marker(&mut stdout, contexts)?;
+                writeln!(stdout)?;
+                stdout.reset()?;
+            }
+
+            if sess_slice.is_empty() {
+                println!("{}", if cli.plain { "No tasks" } else { "  (empty)" });
+                return Ok(());
+            }
+
+            // Индексы сохраняем исходные, чтобы d/r/t попадали в ту же задачу
+            for i in display_order(sess_slice, by_priority || settings.sort_by_priority) {
+                let t = &sess_slice[i];
+                if !shown(t) {
+                    continue;
+                }
+                print_formatted_task(i, t, &options)?;
+                print_subtasks(i, t, &options)?;
+                if speak {
+                    spoken.push(accessibility::plain_task_line(i, t, offset_hours, now));
+                }
+            }
+
+            if speak {
+                let intro = format!("Session {}, {} tasks.", current_session_name, spoken.len());
+                let text = std::iter::once(intro).chain(spoken).collect::<Vec<_>>().join(".\n");
+                accessibility::speak(&text)?;
+            }
+        },
+        Commands::Ll { tag, all, filter } => {
+            let filter = filter.to_filter(offset_hours)?;
+            let contexts: &[String] = if all { &[] } else { &data.active_contexts };
+            let now = Utc::now();
+            let shown = |t: &Task| (all || !t.is_deferred(now)) && t.in_contexts(contexts) && t.has_tags(&tag) && filter.matches(t, now);
+            for (name, tasks) in &data.sessions {
+                numbering::remember(name, tasks)?;
+            }
+
+            if cli.porcelain {
+                porcelain::print_header();
+                let mut names: Vec<_> = data.sessions.keys().collect();
+                names.sort();
+                for name in names {
+                    let tasks = &data.sessions[name];
+                    porcelain::print_session(name, tasks, *name == current_session_name);
+                    for (i, t) in tasks.iter().enumerate().filter(|(_, t)| shown(t)) {
+                        porcelain::print_task(name, i, t);
+                    }
+                }
+                return Ok(());
+            }
+            if data.sessions.is_empty() {
+                println!("No sessions available");
+                return Ok(());
+            }
+
+            let options = ListOptions {
+                offset_hours,
+                vacation: data.vacation.as_ref(),
+                holidays: &settings.time.holidays,
+                big: false,
+                plain: cli.plain,
+                show_scheduled: data.sessions.values().flatten().any(|t| t.scheduled.is_some()),
+                time_width: data.sessions.values().flatten().map(|t| format_range(t, offset_hours).len()).max().unwrap_or(0),
+                tags_column: data.sessions.values().flatten()
+                .map(|t| tags_label(&t.tags).chars().count())
+                .max()
+                .filter(|&width| width > 0),
+                long: false,
+                session: &[],
+            };
+
+            let mut stdout = StandardStream::stdout(theme::color_choice());
+
+            for (session_name, tasks) in &data.sessions {
+                if (!tag.is_empty() || !filter.is_empty()) && !tasks.iter().any(|t| t.has_tags(&tag) && filter.matches(t, now)) {
+                    continue;
+                }
+                let completed = tasks.iter().filter(|t| !t.is_open()).count();
+                let total = tasks.len();
+
+                let is_current = *session_name == current_session_name;
+                let color = if is_current { Color::Green } else { Color::White };
+
+                let status_text = format!(" ({}/{})", completed, total);
+                let marker = if is_current { ">" } else { " " };
+
+                if cli.plain {
+                    print_plain_session_header(session_name, tasks, is_current, contexts);
+                } else {
+                    // Выводим сессию с цветовым выделением
+                    stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(is_current))?; // Выделяем текущую жирным
+                    write!(stdout, "{} {}", marker, session_name)?;
+                    stdout.reset()?;
+
+                    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
+                    write!(stdout, "{}", status_text)?;
+                    print_context_marker(&mut stdout, contexts)?;
+                    writeln!(stdout)?;
+                    stdout.reset()?;
+                }
+
+                if tasks.is_empty() {
+                    println!("{}", if cli.plain { "No tasks" } else { "    (empty)" });
+                    continue;
+                }
+
+                for i in display_order(tasks, settings.sort_by_priority) {
+                    let t = &tasks[i];
+                    if !shown(t) {
+                        continue;
+                    }
+                    if !cli.plain {
+                        let mut stdout = StandardStream::stdout(theme::color_choice());
+                        write!(stdout, "  ")?;
+                        stdout.reset()?;
+                    }
+                    print_formatted_task(i, t, &ListOptions { session: tasks, ..options })?;
+                    print_subtasks(i, t, &options)?;
+                }
+                println!();
+            }
+        },
+        Commands::ServeIcs { filter, session, all, bind } => {
+            let selection = export::Selection {
+                sessions: session,
+                all_sessions: all,
+                filter: filter.as_deref().map(filter::Filter::parse).transpose()?,
+                since: None,
+                local: settings.local_sessions.clone(),
+            };
+            serve::serve_ics(&bind, &selection, &current_session_name, offset_hours)?;
+        },
+        Commands::Ex { format, filter, session, since, all, output, command } => {
+            if !command.is_empty() && !matches!(format, export::Format::Exec) {
+                println!("Extra arguments are only accepted by 'export exec <command>'");
+                return Ok(());
+            }
+
+            let named = if session.is_empty() { std::slice::from_ref(&current_session_name) } else { session.as_slice() };
+            for name in named.iter().filter(|name| !all && settings.local_sessions.contains(name)) {
+                eprintln!("Warning: session '{}' has sync = false and is not exported", name);
+            }
+            let selection = export::Selection {
+                sessions: session.clone(),
+                all_sessions: all,
+                filter: filter.as_deref().map(filter::Filter::parse).transpose()?,
+                since: since.as_deref().map(|s| parse_absolute_time(s, &settings.time)).transpose()?,
+                local: settings.local_sessions.clone(),
+            };
+            let tasks = export::select(data, &current_session_name, &selection);
+            let rendered = export::render(format, &tasks, offset_hours)?;
+
+            if matches!(format, export::Format::Exec) {
+                export::pipe_to_command(&command, &rendered)?;
+                return Ok(());
+            }
+
+            match output {
+                Some(path) => {
+                    fs::write(&path, rendered)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("Exported {} tasks to {}", tasks.len(), path.display());
+                }
+                None => print!("{}", rendered),
+            }
+        },
+        Commands::Ctx { contexts, clear } => {
+            if clear {
+                data.active_contexts.clear();
+                println!("Context cleared, showing all tasks");
+            } else if contexts.is_empty() {
+                if data.active_contexts.is_empty() {
+                    println!("No active context");
+                } else {
+                    println!("Active context: @{}", data.active_contexts.join(" @"));
+                }
+            } else {
+                data.active_contexts = contexts.iter()
+                .map(|c| c.trim_start_matches('@').to_string())
+                .filter(|c| !c.is_empty())
+                .collect();
+                println!("Active context: @{}", data.active_contexts.join(" @"));
+            }
+        },
+        Commands::Loc { query, location } => {
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked => print_locked(idx, &sess[idx]),
+                Some(idx) => {
+                    let location = location.join(" ");
+                    if location.trim().is_empty() {
+                        sess[idx].location = None;
+                        println!("Cleared location of #{} '{}'", shown(&sess[idx], idx), sess[idx].description);
+                    } else {
+                        println!("Set location of #{} '{}' to '{}'", shown(&sess[idx], idx), sess[idx].description, location);
+                        sess[idx].location = Some(location);
+                    }
+                }
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Dif { query, level } => {
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked => print_locked(idx, &sess[idx]),
+                Some(idx) if level == 0 => {
+                    sess[idx].difficulty = None;
+                    println!("Cleared difficulty of #{} '{}'", shown(&sess[idx], idx), sess[idx].description);
+                }
+                Some(idx) => {
+                    sess[idx].difficulty = Some(level);
+                    println!("Set difficulty of #{} '{}' to {}/{}", shown(&sess[idx], idx), sess[idx].description, level, MAX_DIFFICULTY);
+                }
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::P { query, level, clear } => {
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked && (clear || level.is_some()) => print_locked(idx, &sess[idx]),
+                Some(idx) if clear => {
+                    sess[idx].priority = None;
+                    println!("Cleared priority of #{} '{}'", shown(&sess[idx], idx), sess[idx].description);
+                }
+                Some(idx) => match level {
+                    Some(level) => {
+                        sess[idx].priority = Some(level);
+                        println!("Set priority of #{} '{}' to {}", shown(&sess[idx], idx), sess[idx].description, level.label());
+                    }
+                    None => println!(
+                        "Priority of #{} '{}': {}",
+                        shown(&sess[idx], idx),
+                        sess[idx].description,
+                        sess[idx].priority.map_or("none", Priority::label)
+                    ),
+                },
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Est { query, estimate, clear } => {
+            let minutes = match estimate.join(" ") {
+                text if text.is_empty() => None,
+                text => {
+                    let minutes = parse_span(&text)?.num_minutes();
+                    if minutes <= 0 {
+                        anyhow::bail!("Estimate must be at least a minute, e.g. 90m or 2h");
+                    }
+                    Some(u32::try_from(minutes).context("Estimate is too large")?)
+                }
+            };
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked && (clear || minutes.is_some()) => print_locked(idx, &sess[idx]),
+                Some(idx) if clear => {
+                    sess[idx].estimate = None;
+                    println!("Cleared estimate of #{} '{}'", shown(&sess[idx], idx), sess[idx].description);
+                }
+                Some(idx) => match minutes {
+                    Some(minutes) => {
+                        sess[idx].estimate = Some(minutes);
+                        println!("Set estimate of #{} '{}' to {}", shown(&sess[idx], idx), sess[idx].description, format_minutes(minutes));
+                    }
+                    None => println!(
+                        "Estimate of #{} '{}': {}",
+                        shown(&sess[idx], idx),
+                        sess[idx].description,
+                        sess[idx].estimate.map_or("none".to_string(), format_minutes)
+                    ),
+                },
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Ld => {
+            report::load(data, &settings.time);
+        },
+        Commands::Fc { span } => {
+            let span = parse_span(span.as_deref().unwrap_or("30d"))?;
+            if span <= TimeDelta::zero() {
+                println!("Usage: forecast [30d]");
+                return Ok(());
+            }
+            report::forecast(data, offset_hours, span);
+        },
+        Commands::Stats { accuracy: true } => track::accuracy(data),
+        Commands::Stats { accuracy: false } => report::stats(data),
+        Commands::Vac { range, clear } => {
+            if clear {
+                data.vacation = None;
+                println!("Vacation mode off");
+            } else if let Some(range) = range {
+                let vacation = vacation::Vacation::parse(&range)?;
+                let affected = data.sessions.values()
+                .flatten()
+                .filter(|t| t.is_open() && t.time.is_some_and(|time| vacation.contains(time, offset_hours)))
+                .count();
+                data.vacation = Some(vacation);
+                println!("Vacation set: {} .. {} ({} days)", vacation.start, vacation.end, vacation.days());
+                if affected > 0 {
+                    println!("{} tasks are due during your vacation", affected);
+                }
+            } else if let Some(vacation) = &data.vacation {
+                println!("Vacation: {} .. {} ({} days)", vacation.start, vacation.end, vacation.days());
+            } else {
+                println!("Vacation mode is off");
+            }
+        },
+        Commands::Dg { parts } => {
+            if parts.len() < 3 || parts[1] != "to" || (parts.len() > 3 && (parts.len() < 6 || parts[3] != "followup")) {
+                println!("Usage: delegate <index|task_name> to <person> [followup in|at <time>]");
+                return Ok(());
+            }
+            let followup = if parts.len() >= 6 {
+                match parse_prefixed_time(&parts[4], &parts[5..].join(" "), &settings.time)? {
+                    Some(time) => Some(time),
+                    None => return Ok(()),
+                }
+            } else {
+                None
+            };
+
+            let query = &parts[0];
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked => print_locked(idx, &sess[idx]),
+                Some(idx) => {
+                    let task = &mut sess[idx];
+                    task.delegate = Some(parts[2].clone());
+                    task.followup = followup;
+                    println!("Delegated #{} '{}' to {}", shown(task, idx), task.description, parts[2]);
+                    if followup.is_some() {
+                        println!("Follow-up at {}", format_time(&followup, offset_hours));
+                    }
+                }
+                None => print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Sch { parts } => {
+            if parts.is_empty() {
+                println!("Usage: sch <index|task_name> [in|at <time>]");
+                return Ok(());
+            }
+            let scheduled = if parts.len() > 2 {
+                match parse_prefixed_time(&parts[1], &parts[2..].join(" "), &settings.time)? {
+                    Some(time) => Some(time),
+                    None => return Ok(()),
+                }
+            } else {
+                None
+            };
+
+            let query = &parts[0];
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked => print_locked(idx, &sess[idx]),
+                Some(idx) => {
+                    let old = format_time(&sess[idx].scheduled, offset_hours);
+                    sess[idx].scheduled = scheduled;
+                    let new = format_time(&sess[idx].scheduled, offset_hours);
+                    println!("Changed schedule for '{}': {} -> {}", sess[idx].description, old, new);
+                    warn_if_holiday(scheduled, &settings.time);
+                }
+                None => print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Nx => {
+            // Одна строка без цветов, чтобы вставлять в приглашение shell; пусто - нечего делать
+            let sess = data.sessions.get(&current_session_name).map_or(&[][..], |v| v.as_slice());
+            let now = Utc::now();
+            if let Some(idx) = next_action(sess, &data.active_contexts, now) {
+                let task = &sess[idx];
+                if cli.porcelain {
+                    porcelain::print_header();
+                    porcelain::print_task(&current_session_name, idx, task);
+                } else {
+                    let mut line = format!("#{} {}", shown(task, idx), task.description);
+                    if let Some(priority) = task.priority {
+                        line.push_str(&format!(" [!{}]", priority.label()));
+                    }
+                    if task.deadline().is_some() {
+                        line.push_str(&format!(" ({})", accessibility::describe_due(task.deadline(), now)));
+                    }
+                    println!("{}", line);
+                }
+            }
+        },
+        Commands::Df { parts } => {
+            // defer <task> in|at <time>; без времени снимает отсрочку
+            if parts.len() == 2 {
+                println!("Usage: defer <index|task_name> [in|at <time>]");
+                return Ok(());
+            }
+            let deferred = if parts.len() > 2 {
+                match parse_prefixed_time(&parts[1], &parts[2..].join(" "), &settings.time)? {
+                    Some(time) => Some(time),
+                    None => return Ok(()),
+                }
+            } else {
+                None
+            };
+
+            let query = &parts[0];
+            let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, query, match_threshold, strict_comparison);
+
+            match target_idx {
+                Some(idx) if sess[idx].locked => print_locked(idx, &sess[idx]),
+                Some(idx) => {
+                    sess[idx].deferred = deferred;
+                    match deferred {
+                        Some(_) => println!("Deferred '{}' until {}, 'l --all' still shows it", sess[idx].description, format_time(&deferred, offset_hours)),
+                        None => println!("'{}' is no longer deferred", sess[idx].description),
+                    }
                 }
+                None => print_not_found(query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Sr { query, regex } => {
+            let matcher = search::Matcher::new(&query.join(" "), regex)?;
+            search::print(data, &current_session_name, &matcher, match_threshold, offset_hours)?;
+        },
+        Commands::Od { command: OverdueCommand::Report } => {
+            report::overdue_report(data, offset_hours);
+        },
+        Commands::B => {
+            // Строки команд читаются из stdin, отвечать на вопросы там некому.
+            // Номера из l внутри пакета запоминаются только вместе с сохранением данных
+            confirm::set_interactive(false);
+            numbering::defer();
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
 
-                if !data.sessions.contains_key(&session) {
-                    println!("Session '{}' not found", session);
+            for (line_no, line) in input.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
                     continue;
                 }
 
-                let tasks = data.sessions.get(&session).unwrap();
-                let uncompleted_count = tasks.iter().filter(|t| !t.done).count();
-                let total_count = tasks.len();
-
-                if total_count > 0 {
-                    println!("Session '{}' contains {} tasks ({} uncompleted)",
-                             session, total_count, uncompleted_count);
-
-                    if uncompleted_count > 0 {
-                        println!("Are you sure you want to delete this session with uncompleted tasks? [y/N]");
-
-                        let mut input = String::new();
-                        std::io::stdin().read_line(&mut input)?;
-                        let input = input.trim().to_lowercase();
-
-                        if input != "y" && input != "yes" {
-                            println!("Session deletion cancelled for '{}'", session);
-                            continue;
-                        }
-                    } else {
-                        println!("Session contains only completed tasks. Delete anyway? [y/N]");
-
-                        let mut input = String::new();
-                        std::io::stdin().read_line(&mut input)?;
-                        let input = input.trim().to_lowercase();
+                let args = split_command_line(line)
+                .with_context(|| format!("Batch aborted at line {}, nothing was saved", line_no + 1))?;
+                let mut sub = match Cli::try_parse_from(std::iter::once("ttd".to_string()).chain(args)) {
+                    Ok(sub) => sub,
+                    Err(e) => anyhow::bail!("Batch aborted at line {}, nothing was saved:\n{}", line_no + 1, e),
+                };
+                if matches!(sub.command, Some(Commands::B)) {
+                    anyhow::bail!("Batch aborted at line {}: nested batch is not allowed", line_no + 1);
+                }
+                // undo и redo сразу переписывают свой журнал, а пакет целиком отменяется одним undo
+                if matches!(sub.command, Some(Commands::Undo { .. } | Commands::Redo)) {
+                    anyhow::bail!("Batch aborted at line {}: undo and redo cannot run in a batch, nothing was saved", line_no + 1);
+                }
+                sub.plain |= cli.plain;
+                sub.porcelain |= cli.porcelain;
 
-                        if input != "y" && input != "yes" {
-                            println!("Session deletion cancelled for '{}'", session);
-                            continue;
-                        }
+                run_command(sub, data, settings)
+                .with_context(|| format!("Batch aborted at line {} '{}', nothing was saved", line_no + 1, line))?;
+            }
+        },
+        Commands::Tpl { command: TemplateCommand::Save { name, parts, priority } } => {
+            // template save <name> <task> [in|at <time> [for <duration>]] [every <rule>]
+            let mut tags = Vec::new();
+            let description = split_tags(&parts[0], &mut tags);
+            for part in parts[1..].iter().filter(|p| as_tag(p).is_some()) {
+                split_tags(part, &mut tags);
+            }
+            let words: Vec<String> = parts[1..].iter().filter(|p| as_tag(p).is_none()).cloned().collect();
+            if description.is_empty() {
+                anyhow::bail!("Template needs a task description");
+            }
+            let every_pos = words.iter().position(|w| w == "every");
+            let due_words = &words[..every_pos.unwrap_or(words.len())];
+            let due = match due_words {
+                [] => None,
+                [_] => anyhow::bail!("Expected a time after '{}', e.g. in 2d or at fri 17h", due_words[0]),
+                _ => {
+                    // Проверяем сразу, чтобы ошибка всплыла при сохранении, а не при add
+                    if parse_time_range(due_words, &settings.time)?.is_none() {
+                        return Ok(());
                     }
+                    Some(due_words.join(" "))
                 }
-
-                // Удаляем сессию
-                data.sessions.remove(&session);
-
-                // Если удаляемая сессия была текущей - переключаемся на default
-                if Some(&session) == data.current_session.as_ref() {
-                    data.current_session = Some("default".to_string());
-                    println!("Switched to default session");
+            };
+            let every = match every_pos.map(|p| words[p + 1..].join(" ")) {
+                Some(rule) if rule.is_empty() => anyhow::bail!("Expected a rule after 'every', e.g. every 1w"),
+                Some(rule) => {
+                    recurrence::Rule::parse(&rule)?;
+                    Some(rule)
                 }
-
-                println!("Session '{}' deleted successfully", session);
+                None => None,
+            };
+            let template = template::Template { description, tags, priority, due, every };
+            println!("Saved template '{}': {}", name, template.summary());
+            data.templates.insert(name, template);
+        },
+        Commands::Tr { command: TrackCommand::Start { query } } => {
+            let sess = data.sessions.get(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+            let Some(idx) = target_idx else {
+                print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison);
+                return Ok(());
+            };
+            if running_on(data, &current_session_name, idx) {
+                let task = &data.sessions[&current_session_name][idx];
+                println!("Already tracking #{} '{}'", shown(task, idx), task.description);
+                return Ok(());
+            }
+            let now = Utc::now();
+            // Новый таймер останавливает предыдущий
+            if let Some((description, spent)) = track::stop(data, now) {
+                println!("Stopped '{}' after {}", description, format_delta(spent));
             }
+            let task = &mut data.sessions.get_mut(&current_session_name).context("No session")?[idx];
+            task.intervals.push(track::Interval { start: now, end: None });
+            if task.status == Status::Todo {
+                task.status = Status::InProgress;
+            }
+            println!("Tracking #{} '{}'", shown(task, idx), task.description);
         },
-        Commands::D { ref parts } => {
-            handle_done(parts, &mut data, &current_session_name, match_threshold, strict_comparison, true)?;
+        Commands::Tr { command: TrackCommand::Stop } => match track::stop(data, Utc::now()) {
+            Some((description, spent)) => println!("Stopped '{}' after {}", description, format_delta(spent)),
+            None => println!("No timer is running"),
         },
-        Commands::Ud { ref parts } => {
-            handle_done(parts, &mut data, &current_session_name, match_threshold, strict_comparison, false)?;
+        Commands::Tr { command: TrackCommand::Report { by, days } } => track::report(data, by, days, offset_hours),
+        Commands::Trash { command: TrashCommand::List } => trash::print_list(data, offset_hours),
+        Commands::Trash { command: TrashCommand::Empty { older_than } } => {
+            let before = older_than.as_deref().map(parse_span).transpose()?.map(|span| Utc::now() - span);
+            let count = trash::count_before(data, before);
+            if count == 0 {
+                println!("Nothing to empty");
+                return Ok(());
+            }
+            if settings.confirm.is_bulk(count) && !confirm::ask(&format!("Delete {} tasks from trash for good?", count))? {
+                println!("Nothing deleted");
+                return Ok(());
+            }
+            trash::empty(data, before);
+            println!("Deleted {} tasks from trash", count);
         },
-        Commands::T { ref parts } => {
-            if parts.len() < 1 {
-                println!("Usage: t <index|task_name> [in|at] <time>");
+        Commands::Restore { query } => trash::restore(data, &query)?,
+        Commands::Hist { query } => {
+            let sess = data.sessions.get(&current_session_name).map_or(&[][..], |v| v.as_slice());
+            // Удалённые задачи ищем в корзине по id
+            let task = match find_task(&current_session_name, sess, &query, match_threshold, strict_comparison) {
+                (Some(idx), _, _) => &sess[idx],
+                (None, match_info, is_index_search) => {
+                    let id = query.trim_start_matches('=').to_lowercase();
+                    match data.trash.iter().find(|e| e.task.short_id().is_some_and(|s| s == id)) {
+                        Some(entry) => &entry.task,
+                        None => {
+                            print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison);
+                            return Ok(());
+                        }
+                    }
+                }
+            };
+            let Some(id) = task.id else {
+                println!("'{}' has no history yet", task.description);
                 return Ok(());
+            };
+            println!("History of '{}' (={})", task.description, short_id(id));
+            history::print(id, offset_hours)?;
+        },
+        Commands::Undo { list: true } => undo::print_list(offset_hours)?,
+        Commands::Undo { list: false } => undo::undo(data)?,
+        Commands::Redo => undo::redo(data)?,
+        Commands::Tpl { command: TemplateCommand::List } => template::print_list(&data.templates, &settings.templates),
+        Commands::Tpl { command: TemplateCommand::Rm { name } } => match data.templates.remove(&name) {
+            Some(_) => println!("Removed template '{}'", name),
+            None if settings.templates.contains_key(&name) => println!("Template '{}' is defined in config.toml, remove it there", name),
+            None => println!("Template '{}' not found", name),
+        },
+        Commands::Gh { command: GitHookCommand::Install { force } } => githook::install(force)?,
+        Commands::Gh { command: GitHookCommand::Check } => {
+            // Сессия проекта: .ttd или имя репозитория, даже если session_from_git выключен
+            let session = find_session_marker()
+            .map(|(_, name)| name)
+            .or_else(|| git_session_name(settings.git_session.unwrap_or(false)))
+            .unwrap_or(current_session_name);
+            let tasks = data.sessions.get(&session).map_or(&[][..], |v| v.as_slice());
+            githook::check(&session, tasks, settings.git_hook_blocks)?;
+        },
+        Commands::Fsck { .. } => anyhow::bail!("fsck works on the data file directly and cannot run in a batch"),
+        Commands::Selftest => anyhow::bail!("selftest cannot run in a batch"),
+        Commands::O { query, editor } => {
+            let sess = data.sessions.get(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
+
+            match target_idx {
+                // Вложения вида "файл:строка" оставляет scan; у ссылок тоже бывает ":8080", их пропускаем
+                Some(idx) if editor => {
+                    let place = sess[idx].attachments.iter().filter(|a| !a.contains("://")).find_map(|a| {
+                        let (file, line) = a.rsplit_once(':')?;
+                        if file.is_empty() || !line.chars().all(|c| c.is_ascii_digit()) {
+                            return None;
+                        }
+                        Some((file, line.parse::<usize>().ok()?))
+                    });
+                    match place {
+                        Some((file, line)) => {
+                            println!("Opening {}:{}", file, line);
+                            open_in_editor_at(file, line)?;
+                        }
+                        None => println!("Task #{} '{}' has no file:line attachment", shown(&sess[idx], idx), sess[idx].description),
+                    }
+                }
+                Some(idx) => match links::first_url(&sess[idx].description).or_else(|| {
+                    sess[idx].attachments.iter().map(String::as_str).find(|a| is_url(a))
+                }) {
+                    Some(url) => {
+                        println!("Opening {}", url);
+                        links::open_url(url)?;
+                    }
+                    None => println!("Task #{} '{}' has no links", shown(&sess[idx], idx), sess[idx].description),
+                },
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
+            }
+        },
+        Commands::Lk { query, urls, clear } => {
+            if let Some(bad) = urls.iter().find(|u| !is_url(u)) {
+                anyhow::bail!("'{}' is not a link, expected http:// or https://", bad);
             }
-            let query = &parts[0];
             let sess = data.sessions.get_mut(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
 
-            let (target_idx, match_info, is_index_search) = find_task(sess, query, match_threshold, strict_comparison);
-
-            if let Some(idx) = target_idx {
-                let time = if parts.len() > 2 {
-                    let prefix = &parts[1];
-                    let time_str = &parts[2];
-
-                    if prefix == "in" {
-                        Some(parse_relative_time(time_str)?)
-                    } else if prefix == "at" {
-                        Some(parse_absolute_time(time_str, offset_hours)?)
-                    } else {
-                        println!("Unknown time prefix '{}'. Use 'in' for relative time or 'at' for absolute time.", prefix);
-                        return Ok(());
+            match target_idx {
+                Some(idx) if sess[idx].locked && (clear || !urls.is_empty()) => print_locked(idx, &sess[idx]),
+                Some(idx) if clear => {
+                    sess[idx].attachments.retain(|a| !is_url(a));
+                    println!("Removed links from #{} '{}'", shown(&sess[idx], idx), sess[idx].description);
+                }
+                Some(idx) if urls.is_empty() => {
+                    let task = &sess[idx];
+                    let links: Vec<&String> = task.attachments.iter().filter(|a| is_url(a)).collect();
+                    if links.is_empty() {
+                        println!("Task #{} '{}' has no links", shown(task, idx), task.description);
                     }
-                } else {
-                    None
-                };
-                let old_time = format_time(&sess[idx].time, offset_hours);
-                sess[idx].time = time;
-                let new_time = format_time(&sess[idx].time, offset_hours);
-                println!("Changed time for '{}': {} -> {}", sess[idx].description, old_time, new_time);
-            } else {
-                if !is_index_search {
-                    if let Some((matched_desc, score)) = match_info {
-                        if strict_comparison {
-                            println!("No exact match found for '{}'", query);
-                            println!("Possible match: \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
-                            println!("To enable fuzzy matching, set strict_comparison=false in config.toml");
-                        } else {
-                            println!("No task found matching \"{}\" with confidence > {:.0}%", query, match_threshold * 100.0);
-                            println!("Closest match was \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
-                            println!("To enable strict matching, set strict_comparison=true in config.toml");
+                    for url in links {
+                        println!("{}", url);
+                    }
+                }
+                Some(idx) => {
+                    let task = &mut sess[idx];
+                    for url in urls {
+                        if !task.attachments.contains(&url) {
+                            task.attachments.push(url);
                         }
-                    } else {
-                        println!("Task '{}' not found", query);
                     }
-                } else {
-                    println!("Index {} not found", query);
+                    println!("Task #{} '{}' now has {} link(s)", shown(task, idx), task.description, task.attachments.iter().filter(|a| is_url(a)).count());
                 }
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
             }
-            sort_tasks(sess);
-        }
-        Commands::L => {
-
-            let color = Color::Green;
-
-            let sess_slice = data.sessions.get(&current_session_name).map_or(&[][..], |v| v.as_slice());
-            let completed = sess_slice.iter().filter(|t| t.done).count();
-            let total = sess_slice.len();
-
-            let status_text = format!(" ({}/{})", completed, total);
-            let marker = ">";
-
-            let mut stdout = StandardStream::stdout(ColorChoice::Always);
-
-            // Выводим сессию с цветовым выделением
-            stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?; // Выделяем текущую жирным
-            write!(stdout, "{} {}", marker, &current_session_name)?;
-            stdout.reset()?;
-
-            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
-            writeln!(stdout, "{}", status_text)?;
-            stdout.reset()?;
-
-            if sess_slice.is_empty() {
-                println!("  (empty)");
+        },
+        Commands::Pr { session } => {
+            let name = session.unwrap_or_else(|| current_session_name.clone());
+            match data.sessions.get(&name) {
+                Some(tasks) => report::present(&name, tasks, offset_hours),
+                None => println!("Session '{}' not found", name),
+            }
+        },
+        Commands::Gt { session, days } => {
+            let name = session.unwrap_or_else(|| current_session_name.clone());
+            match data.sessions.get(&name) {
+                Some(tasks) => print!("{}", gantt::render(&name, tasks, &settings.time, days)),
+                None => println!("Session '{}' not found", name),
+            }
+        },
+        Commands::Wh { parts } => {
+            // Ничего не меняет: только показывает, как разбирается время
+            let parts: Vec<String> = parts.iter().flat_map(|p| p.split_whitespace()).map(str::to_string).collect();
+            if parts.len() < 2 {
+                println!("Usage: when in|at <time> [for <duration>] or when from <time> to <time>");
+                return Ok(());
+            }
+            let Some((start, end)) = parse_time_range(&parts, &settings.time)? else {
                 return Ok(());
+            };
+            let offset = TimeDelta::hours(offset_hours);
+            let now = Utc::now();
+            println!("{}", (start + offset).format("%Y-%m-%d %H:%M %A"));
+            if let Some(name) = settings.time.holidays.name_at(start, offset_hours) {
+                println!("holiday: {}", name);
             }
+            if let Some(end) = end {
+                println!("until {} ({})", (end + offset).format("%Y-%m-%d %H:%M %A"), format_delta(end - start));
+            }
+            if start >= now {
+                println!("in {}", format_delta(start - now));
+            } else {
+                println!("{} ago", format_delta(now - start));
+            }
+        },
+        Commands::Cd { query, flash } => {
+            let flash = match flash {
+                Some(f) => Some(parse_duration(&f).with_context(|| format!("Invalid duration '{}', use e.g. 10m or 1h", f))?),
+                None => None,
+            };
+            let sess = data.sessions.get(&current_session_name).context("No session")?;
+            let (target_idx, match_info, is_index_search) = find_task(&current_session_name, sess, &query, match_threshold, strict_comparison);
 
-            for (i, t) in sess_slice.iter().enumerate() {
-                print_formatted_task(i, t, offset_hours)?;
+            match target_idx {
+                Some(idx) if !sess[idx].is_open() => println!("Task #{} '{}' is already {}", shown(&sess[idx], idx), sess[idx].description, sess[idx].status.label()),
+                Some(idx) => match sess[idx].time {
+                    Some(due) => countdown::run(&sess[idx].description, due, flash, settings.time.offset_hours)?,
+                    None => println!("Task #{} '{}' has no due time", shown(&sess[idx], idx), sess[idx].description),
+                },
+                None => print_not_found(&query, match_info, is_index_search, match_threshold, strict_comparison),
             }
         },
-        Commands::Ll => {
-            if data.sessions.is_empty() {
-                println!("No sessions available");
-                return Ok(());
+        Commands::Pomo { query, length, waited } => {
+            // Обычно отсчёт уже прошёл в run(), до загрузки данных; в пакетном режиме идёт здесь
+            let (id, start, description) = match waited {
+                Some(waited) => waited,
+                None => {
+                    let Some((id, description)) = pomodoro_target(data, &current_session_name, &query, settings) else {
+                        return Ok(());
+                    };
+                    let start = wait_pomodoro(&description, &length, offset_hours)?;
+                    (id, start, description)
+                }
+            };
+            // Задачу могли перенести в другую сессию, пока шёл отсчёт
+            match data.sessions.values_mut().flatten().find(|t| t.id == Some(id)) {
+                Some(task) => {
+                    task.pomodoros += 1;
+                    task.intervals.push(track::Interval { start, end: Some(Utc::now()) });
+                    println!("Pomodoro logged on '{}', {} so far", task.description, task.pomodoros);
+                }
+                None => println!("Task '{}' was removed meanwhile, pomodoro not logged", description),
             }
+        },
+        Commands::Sc { path, session, dry_run } => {
+            let target = session.unwrap_or_else(|| current_session_name.clone());
+            let report = if dry_run {
+                let mut scratch = data.sessions.get(&target).cloned().unwrap_or_default();
+                import::scan_todos(&path, &mut scratch)?
+            } else {
+                let sess = data.sessions.entry(target.clone()).or_default();
+                import::scan_todos(&path, sess)?
+            };
+            report.print(&format!("TODO(ttd) in {} -> '{}'", path.display(), target), dry_run);
+        },
+        Commands::Im { source } => match source {
+            ImportSource::Checklist { file, session, dry_run } => {
+                let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+                let target = session.unwrap_or_else(|| current_session_name.clone());
 
-            let mut stdout = StandardStream::stdout(ColorChoice::Always);
+                let report = if dry_run {
+                    let mut scratch = data.sessions.get(&target).cloned().unwrap_or_default();
+                    import::import_checklist(&content, &mut scratch, &settings.time)
+                } else {
+                    let sess = data.sessions.entry(target.clone()).or_default();
+                    import::import_checklist(&content, sess, &settings.time)
+                };
+                report.print(&format!("{} -> '{}'", file.display(), target), dry_run);
+            }
+            ImportSource::Json { file, map, session, dry_run } => {
+                let map = import::parse_map(&map, &settings.import_maps)?;
+                let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+                let target = session.unwrap_or_else(|| current_session_name.clone());
 
-            for (session_name, tasks) in &data.sessions {
-                let completed = tasks.iter().filter(|t| t.done).count();
-                let total = tasks.len();
+                let report = if dry_run {
+                    let mut scratch = data.sessions.clone();
+                    import::import_json(&content, &map, &target, &mut scratch, &settings.time)?
+                } else {
+                    import::import_json(&content, &map, &target, &mut data.sessions, &settings.time)?
+                };
+                report.print(&format!("{} -> '{}'", file.display(), target), dry_run);
+            }
+        },
+    }
 
-                let is_current = Some(session_name.as_str()) == data.current_session.as_deref();
-                let color = if is_current { Color::Green } else { Color::White };
+    Ok(())
+}
 
-                let status_text = format!(" ({}/{})", completed, total);
-                let marker = if is_current { ">" } else { " " };
+// Зависит ли задача from (прямо или через другие) от задачи target
+fn depends_on(tasks: &[Task], from: u64, target: u64) -> bool {
+    let mut stack = vec![from];
+    let mut seen = Vec::new();
+    while let Some(id) = stack.pop() {
+        if id == target {
+            return true;
+        }
+        if seen.contains(&id) {
+            continue;
+        }
+        seen.push(id);
+        if let Some(task) = tasks.iter().find(|t| t.id == Some(id)) {
+            stack.extend(&task.blocked_by);
+        }
+    }
+    false
+}
 
-                // Выводим сессию с цветовым выделением
-                stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(is_current))?; // Выделяем текущую жирным
-                write!(stdout, "{} {}", marker, session_name)?;
-                stdout.reset()?;
+// [1, 3] -> "#1, #3"
+fn index_list(indices: &[usize]) -> String {
+    indices.iter().map(|i| format!("#{}", i)).collect::<Vec<_>>().join(", ")
+}
 
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
-                writeln!(stdout, "{}", status_text)?;
-                stdout.reset()?;
+// "2-6" -> 2 3 4 5 6, если так не называется ни одна задача
+fn expand_ranges(tasks: &[Task], parts: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for arg in parts {
+        let range = arg.split_once('-')
+        .filter(|_| !tasks.iter().any(|t| &t.description == arg))
+        .and_then(|(from, to)| Some((from.parse::<usize>().ok()?, to.parse::<usize>().ok()?)));
+        match range {
+            Some((from, to)) if from > to => anyhow::bail!("Range {} goes backwards, did you mean {}-{}?", arg, to, from),
+            Some((from, to)) if to - from >= tasks.len() => anyhow::bail!("Range {} is longer than the list of {} tasks", arg, tasks.len()),
+            Some((from, to)) => expanded.extend((from..=to).map(|i| i.to_string())),
+            None => expanded.push(arg.clone()),
+        }
+    }
+    Ok(expanded)
+}
 
-                if tasks.is_empty() {
-                    println!("    (empty)");
-                    continue;
-                }
+// "2.1" - подзадача 1 задачи 2
+fn parse_subtask_ref(session: &str, tasks: &[Task], arg: &str) -> Option<(usize, usize)> {
+    let (parent, child) = arg.split_once('.')?;
+    Some((find_by_index(session, tasks, parent.parse().ok()?)?, child.parse().ok()?))
+}
 
-                for (i, t) in tasks.iter().enumerate() {
-                    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-                    write!(stdout, "  ")?;
-                    stdout.reset()?;
-                    print_formatted_task(i, t, offset_hours)?;
-                }
-                println!();
+fn handle_pin(parts: &[String], data: &mut Data, current: &str, settings: &Settings, pinned: bool) -> Result<()> {
+    let (threshold, strict) = (settings.match_threshold, settings.strict_comparison);
+    let sess = data.sessions.get_mut(current).context("No session")?;
+
+    // Сначала находим все задачи: после сортировки индексы поменяются
+    let mut targets = Vec::new();
+    for arg in parts {
+        match find_task(current, sess, arg, threshold, strict) {
+            (Some(idx), _, _) => targets.push(idx),
+            (None, match_info, is_index_search) => print_not_found(arg, match_info, is_index_search, threshold, strict),
+        }
+    }
+    for idx in targets {
+        let verb = if pinned { "pinned" } else { "unpinned" };
+        if sess[idx].pinned == pinned {
+            println!("Task #{} '{}' is already {}", shown(&sess[idx], idx), sess[idx].description, verb);
+        } else {
+            sess[idx].pinned = pinned;
+            println!("Task #{} '{}' {}", shown(&sess[idx], idx), sess[idx].description, verb);
+        }
+    }
+    sort_tasks(sess);
+    Ok(())
+}
+
+fn handle_lock(parts: &[String], data: &mut Data, current: &str, settings: &Settings, locked: bool) -> Result<()> {
+    let (threshold, strict) = (settings.match_threshold, settings.strict_comparison);
+    let sess = data.sessions.get_mut(current).context("No session")?;
+    let verb = if locked { "locked" } else { "unlocked" };
+
+    for arg in parts {
+        match find_task(current, sess, arg, threshold, strict) {
+            (Some(idx), _, _) if sess[idx].locked == locked => println!("Task #{} '{}' is already {}", shown(&sess[idx], idx), sess[idx].description, verb),
+            (Some(idx), _, _) => {
+                sess[idx].locked = locked;
+                println!("Task #{} '{}' {}", shown(&sess[idx], idx), sess[idx].description, verb);
             }
-        },
+            (None, match_info, is_index_search) => print_not_found(arg, match_info, is_index_search, threshold, strict),
+        }
+    }
+    Ok(())
+}
+
+// tag add|rm <tag> [task...] [--filter <expr>]: задачи из списка и все, что подходят под фильтр
+fn handle_tag(tag: &str, parts: &[String], filter: Option<&str>, data: &mut Data, current: &str, settings: &Settings, add: bool) -> Result<()> {
+    let tag = tags::parse(tag)?;
+    if parts.is_empty() && filter.is_none() {
+        println!("Usage: tag add|rm <+tag> <index|task_name> [...] [--filter <expr>]");
+        return Ok(());
+    }
+    let filter = filter.map(filter::Filter::parse).transpose()?;
+    let (threshold, strict) = (settings.match_threshold, settings.strict_comparison);
+    let sess = data.sessions.get_mut(current).context("No session")?;
+
+    let mut targets = Vec::new();
+    for arg in parts {
+        match find_task(current, sess, arg, threshold, strict) {
+            (Some(idx), _, _) => targets.push(idx),
+            (None, match_info, is_index_search) => print_not_found(arg, match_info, is_index_search, threshold, strict),
+        }
+    }
+    if let Some(filter) = &filter {
+        let now = Utc::now();
+        targets.extend((0..sess.len()).filter(|&i| filter.matches(&sess[i], now)));
+    }
+    targets.sort_unstable();
+    targets.dedup();
+    let has_tag = |t: &Task| t.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag));
+    targets.retain(|&i| has_tag(&sess[i]) != add);
+    if targets.is_empty() {
+        println!("No tasks to {} #{}", if add { "tag with" } else { "untag from" }, tag);
+        return Ok(());
     }
 
-    save_data(&data)?;
+    let verb = if add { "Tag" } else { "Untag" };
+    if settings.confirm.is_bulk(targets.len()) && !confirm::ask(&format!("{} {} tasks?", verb, targets.len()))? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+    for idx in targets {
+        let task = &mut sess[idx];
+        if task.locked {
+            print_locked(idx, task);
+            continue;
+        }
+        if add {
+            task.tags.push(tag.clone());
+            println!("Tagged #{} '{}' with #{}", shown(task, idx), task.description, tag);
+        } else {
+            task.tags.retain(|t| !t.eq_ignore_ascii_case(&tag));
+            println!("Removed #{} from #{} '{}'", tag, shown(task, idx), task.description);
+        }
+    }
     Ok(())
 }
 
-fn handle_remove(parts: &[String], data: &mut Data, current: &str, threshold: f64, strict: bool) -> Result<()> {
+fn print_locked(idx: usize, task: &Task) {
+    let number = shown(task, idx);
+    println!("Task #{} '{}' is locked, 'unlock {}' first", number, task.description, number);
+}
+
+fn handle_remove(parts: &[String], data: &mut Data, current: &str, settings: &Settings) -> Result<()> {
     if parts.is_empty() {
-        println!("Usage: r <index|task_name> [...]");
+        println!("Usage: r <index|task_name|index.subtask> [...]");
         return Ok(());
     }
+    let (threshold, strict) = (settings.match_threshold, settings.strict_comparison);
     let sess = data.sessions.get_mut(current).context("No session")?;
+    let parts = expand_ranges(sess, parts)?;
 
     let mut indices_to_remove = Vec::new();
+    let mut subtasks_to_remove = Vec::new();
     let mut not_found = Vec::new();
 
-    for arg in parts {
-        let (target_idx, match_info, is_index_search) = find_task(sess, arg, threshold, strict);
-        if let Some(idx) = target_idx {
+    for arg in &parts {
+        if let Some((parent, child)) = parse_subtask_ref(current, sess, arg) {
+            if sess.get(parent).is_some_and(|p| p.locked) {
+                print_locked(parent, &sess[parent]);
+            } else if sess.get(parent).is_some_and(|p| child < p.subtasks.len()) {
+                subtasks_to_remove.push((parent, child));
+            } else {
+                not_found.push((arg.clone(), None, true));
+            }
+            continue;
+        }
+        let (target_idx, match_info, is_index_search) = find_task(current, sess, arg, threshold, strict);
+        if let Some(idx) = target_idx.filter(|&i| sess[i].locked) {
+            print_locked(idx, &sess[idx]);
+        } else if let Some(idx) = target_idx {
+            let fuzzy = match_info.is_some_and(|(_, score)| score < 1.0);
+            if fuzzy && settings.confirm.fuzzy_destructive && !confirm::ask(&format!("'{}' matched '{}', remove it?", arg, sess[idx].description))? {
+                println!("Skipped '{}'", sess[idx].description);
+                continue;
+            }
             indices_to_remove.push(idx);
         } else {
             not_found.push((arg.clone(), match_info, is_index_search));
@@ -778,11 +4178,45 @@ fn handle_remove(parts: &[String], data: &mut Data, current: &str, threshold: f6
     indices_to_remove.dedup(); // Удаление дубликатов
     indices_to_remove.sort_by(|a, b| b.cmp(a)); // Сортировка по убыванию
 
+    // Подзадачи удаляем раньше задач, пока индексы родителей не сдвинулись
+    subtasks_to_remove.sort_unstable();
+    subtasks_to_remove.dedup();
+
+    let count = indices_to_remove.len() + subtasks_to_remove.len();
+    if count > 0 && (settings.confirm.remove_task || settings.confirm.is_bulk(count)) {
+        let names: Vec<String> = indices_to_remove.iter().rev().map(|&i| format!("#{} '{}'", shown(&sess[i], i), sess[i].description))
+        .chain(subtasks_to_remove.iter().map(|&(p, c)| format!("#{}.{} '{}'", shown(&sess[p], p), c, sess[p].subtasks[c].description)))
+        .collect();
+        if !confirm::ask(&format!("Remove {}?", names.join(", ")))? {
+            println!("Nothing removed");
+            return Ok(());
+        }
+    }
+    let now = Utc::now();
+    for &(parent, child) in subtasks_to_remove.iter().rev() {
+        let removed = sess[parent].subtasks.remove(child);
+        println!("Removed subtask #{}.{} '{}'", shown(&sess[parent], parent), child, removed.description);
+        data.trash.push(TrashEntry {
+            session: current.to_string(),
+            note: Some(format!("subtask of '{}'", sess[parent].description)),
+            task: removed,
+            deleted_at: now,
+        });
+    }
+
     for &idx in &indices_to_remove {
         if idx < sess.len() {
-            let desc = sess[idx].description.clone();
-            sess.remove(idx);
-            println!("Removed task #{} '{}'", idx, desc);
+            let removed = sess.remove(idx);
+            match removed.short_id() {
+                Some(id) => println!("Removed task #{} '{}', 'restore {}' brings it back", shown(&removed, idx), removed.description, id),
+                None => println!("Removed task #{} '{}'", shown(&removed, idx), removed.description),
+            }
+            data.trash.push(TrashEntry {
+                session: current.to_string(),
+                note: None,
+                task: removed,
+                deleted_at: now,
+            });
         } else {
             eprintln!("Warning: index {} out of bounds after previous removals", idx);
         }
@@ -790,73 +4224,172 @@ fn handle_remove(parts: &[String], data: &mut Data, current: &str, threshold: f6
 
     // Вывод сообщений о ненайденных задачах
     for (arg, match_info, is_index_search) in not_found {
-        if is_index_search {
-            println!("Index {} not found", arg);
-        } else {
-            if let Some((matched_desc, score)) = match_info {
-                if strict {
-                    println!("No exact match found for '{}'", arg);
-                    println!("Possible match: \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
-                    println!("To enable fuzzy matching, set strict_comparison=false in config.toml");
-                } else {
-                    println!("No task found matching \"{}\" with confidence > {:.0}%", arg, threshold * 100.0);
-                    println!("Closest match was \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
-                    println!("To enable strict matching, set strict_comparison=true in config.toml");
-                }
-            } else {
-                println!("Task '{}' not found", arg);
-            }
-        }
+        print_not_found(&arg, match_info, is_index_search, threshold, strict);
     }
 
     // Сортировка после удаления не требуется, так как порядок не меняется
     Ok(())
 }
 
-fn handle_done(parts: &[String], data: &mut Data, current: &str, threshold: f64, strict: bool, mark_done: bool) -> Result<()> {
+// Общая часть d, ud, start, wait, cancel и skip (skip - пропустить один раз повторяющуюся задачу)
+fn handle_done(parts: &[String], data: &mut Data, current: &str, settings: &Settings, status: Status, force: bool, skip: bool) -> Result<()> {
     if parts.is_empty() {
-        println!("Usage: {} <index|task_name> [...]", if mark_done { "d" } else { "ud" });
+        let command = match status {
+            _ if skip => "skip",
+            Status::Todo => "ud",
+            Status::InProgress => "start",
+            Status::Waiting => "wait",
+            Status::Done => "d",
+            Status::Cancelled => "cancel",
+        };
+        println!("Usage: {} <index|task_name|index.subtask> [...]", command);
         return Ok(());
     }
+    let state = if status == Status::Todo { "NOT done" } else { status.label() };
+    let (threshold, strict) = (settings.match_threshold, settings.strict_comparison);
+    let offset_hours = settings.time.offset_hours;
     let sess = data.sessions.get_mut(current).context("No session")?;
+    let parts = expand_ranges(sess, parts)?;
 
+    // Сначала находим задачи: подтверждение считает их, а не аргументы
+    let mut targets: Vec<(usize, Option<usize>)> = Vec::new();
     let mut not_found = Vec::new();
+    for arg in &parts {
+        let target = match parse_subtask_ref(current, sess, arg) {
+            Some((parent, child)) if sess.get(parent).is_some_and(|p| child < p.subtasks.len()) => (parent, Some(child)),
+            Some(_) => {
+                not_found.push((arg.clone(), None, true));
+                continue;
+            }
+            None => match find_task(current, sess, arg, threshold, strict) {
+                (Some(idx), _, _) => (idx, None),
+                (None, match_info, is_index_search) => {
+                    not_found.push((arg.clone(), match_info, is_index_search));
+                    continue;
+                }
+            },
+        };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    let question = if skip { format!("Skip {} tasks?", targets.len()) } else { format!("Mark {} tasks as {}?", targets.len(), state) };
+    if settings.confirm.is_bulk(targets.len()) && !confirm::ask(&question)? {
+        println!("Nothing changed");
+        return Ok(());
+    }
 
-    for arg in parts {
-        let (target_idx, match_info, is_index_search) = find_task(sess, arg, threshold, strict);
+    let mut rescheduled = false;
+    let mut touched_parents = Vec::new();
+    let mut completed_ids = Vec::new();
+
+    for (idx, child) in targets {
+        if sess[idx].locked {
+            print_locked(idx, &sess[idx]);
+            continue;
+        }
+        let (desc, number) = (sess[idx].description.clone(), shown(&sess[idx], idx));
+        if let Some(child) = child {
+            let sub = &mut sess[idx].subtasks[child];
+            if sub.status != status {
+                sub.status = status;
+                sub.completed_at = (status == Status::Done).then(Utc::now);
+                println!("Marked #{}.{} '{}' as {}", number, child, sub.description, state);
+                touched_parents.push(idx);
+            } else {
+                println!("Subtask #{}.{} '{}' is already {}", number, child, sub.description, state);
+            }
+            continue;
+        }
 
-        if let Some(idx) = target_idx {
-            let desc = sess[idx].description.clone();
-            if sess[idx].done != mark_done {
-                sess[idx].done = mark_done;
-                println!("Marked #{} '{}' as {}", idx, desc, if mark_done { "done" } else { "NOT done" });
+        let blockers = sess[idx].open_blockers(sess);
+        if status == Status::Done && !skip && !force && !blockers.is_empty() {
+            let blockers: Vec<usize> = blockers.iter().map(|&i| shown(&sess[i], i)).collect();
+            println!("Task #{} '{}' is blocked by {}; finish those first or use --force", number, desc, index_list(&blockers));
+            continue;
+        }
+        let rule = sess[idx].recurrence.as_deref().map(recurrence::Rule::parse).transpose()?;
+        // Повторяющаяся задача после d и skip не закрывается, а переезжает на следующий раз.
+        // cancel заканчивает всю серию
+        if let Some(rule) = rule.filter(|_| (skip || status == Status::Done) && sess[idx].is_open()) {
+            let now = Utc::now();
+            let next = rule.next(sess[idx].time.unwrap_or(now), now, offset_hours);
+            sess[idx].move_to(Some(next));
+            sess[idx].status = Status::Todo;
+            rescheduled = true;
+            let verb = if skip { "Skipped" } else { "Completed" };
+            println!("{} #{} '{}', next time {}", verb, number, desc, format_time(&Some(next), offset_hours));
+        } else if skip {
+            println!("Task #{} '{}' does not repeat, nothing to skip", number, desc);
+        } else if sess[idx].status != status || (status == Status::Cancelled && sess[idx].recurrence.is_some()) {
+            sess[idx].status = status;
+            sess[idx].completed_at = (status == Status::Done).then(Utc::now);
+            if status == Status::Cancelled && sess[idx].recurrence.take().is_some() {
+                println!("Cancelled #{} '{}', it will not repeat anymore", number, desc);
             } else {
-                println!("Task #{} '{}' is already {}", idx, desc, if mark_done { "done" } else { "NOT done" });
+                println!("Marked #{} '{}' as {}", number, desc, state);
+            }
+            if !status.is_open() {
+                completed_ids.extend(sess[idx].id);
             }
         } else {
-            not_found.push((arg.clone(), match_info, is_index_search));
+            println!("Task #{} '{}' is already {}", number, desc, state);
         }
     }
 
     for (arg, match_info, is_index_search) in not_found {
-        if is_index_search {
-            println!("Index {} not found", arg);
-        } else {
-            if let Some((matched_desc, score)) = match_info {
-                if strict {
-                    println!("No exact match found for '{}'", arg);
-                    println!("Possible match: \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
-                    println!("To enable fuzzy matching, set strict_comparison=false in config.toml");
-                } else {
-                    println!("No task found matching \"{}\" with confidence > {:.0}%", arg, threshold * 100.0);
-                    println!("Closest match was \"{}\" (confidence: {:.1}%)", matched_desc, score * 100.0);
-                    println!("To enable strict matching, set strict_comparison=true in config.toml");
-                }
-            } else {
-                println!("Task '{}' not found", arg);
+        print_not_found(&arg, match_info, is_index_search, threshold, strict);
+    }
+
+    for (i, task) in sess.iter().enumerate() {
+        if task.blocked_by.iter().any(|id| completed_ids.contains(id)) && task.open_blockers(sess).is_empty() && task.is_open() {
+            println!("Task #{} '{}' is no longer blocked", shown(task, i), task.description);
+        }
+    }
+
+    // Родитель закрывается вместе с последней подзадачей и открывается обратно.
+    // Повторяющиеся задачи не трогаем: их закрытие - это перенос срока
+    if settings.auto_complete_parent {
+        touched_parents.sort_unstable();
+        touched_parents.dedup();
+        for parent in touched_parents {
+            let task = &mut sess[parent];
+            let all_done = task.subtasks.iter().all(|t| !t.is_open());
+            if task.recurrence.is_none() && task.is_open() == all_done {
+                task.status = if all_done { Status::Done } else { Status::Todo };
+                task.completed_at = all_done.then(Utc::now);
+                println!("{} #{} '{}'", if all_done { "All subtasks done, completed" } else { "Reopened" }, shown(task, parent), task.description);
             }
         }
     }
 
+    // Сортируем только после цикла, иначе индексы следующих аргументов съедут
+    if rescheduled {
+        sort_tasks(sess);
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_ids_are_stable_and_unique() {
+        assert_eq!(short_id(26), "2832");
+        assert_eq!(short_id(1).len(), 4);
+        let ids: std::collections::HashSet<String> = (1..=5000).map(short_id).collect();
+        assert_eq!(ids.len(), 5000);
+        // Без похожих на цифры i, l, o, u
+        assert!(ids.iter().all(|id| !id.contains(['i', 'l', 'o', 'u'])));
+    }
+
+    #[test]
+    fn ids_are_found_case_insensitively() {
+        let tasks = vec![Task { id: Some(26), ..Default::default() }, Task { id: Some(27), ..Default::default() }];
+        assert_eq!(find_by_id(&tasks, "2832"), Some(0));
+        assert_eq!(find_by_id(&tasks, &short_id(27).to_uppercase()), Some(1));
+        assert_eq!(find_by_id(&tasks, "zzzz"), None);
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use std::fs;
+use std::path::Path;
+
+use crate::{split_tags, Data, Status, Task, TrashEntry};
+
+const HEADER: &str = "# ttd mirror: edit freely, changes are read back on the next ttd run.\n\
+# [ ] todo  [>] in progress  [?] waiting  [x] done  [-] cancelled\n\
+# due:YYYY-MM-DDTHH:MM is local time, =id links a line to its task, lines without id become new tasks\n";
+const SESSION_PREFIX: &str = "## ";
+const DUE_PREFIX: &str = "due:";
+const DUE_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+fn checkbox(status: Status) -> &'static str {
+    match status {
+        Status::Todo => "[ ]",
+        Status::InProgress => "[>]",
+        Status::Waiting => "[?]",
+        Status::Done => "[x]",
+        Status::Cancelled => "[-]",
+    }
+}
+
+fn parse_checkbox(text: &str) -> Option<Status> {
+    match text {
+        "[ ]" => Some(Status::Todo),
+        "[>]" => Some(Status::InProgress),
+        "[?]" => Some(Status::Waiting),
+        "[x]" | "[X]" => Some(Status::Done),
+        "[-]" => Some(Status::Cancelled),
+        _ => None,
+    }
+}
+
+// FNV-1a: хеш должен совпадать между версиями компилятора, DefaultHasher этого не обещает
+pub fn hash(text: impl AsRef<[u8]>) -> u64 {
+    text.as_ref().iter().fold(0xcbf29ce484222325, |h, &b| (h ^ u64::from(b)).wrapping_mul(0x100000001b3))
+}
+
+// Сессии по имени, задачи в порядке списка; подзадачи и прочие поля в файл не попадают,
+// сессии с sync = false (local) тоже
+pub fn render(data: &Data, local: &[String], offset_hours: i64) -> String {
+    let mut out = HEADER.to_string();
+    let mut names: Vec<&String> = data.sessions.keys().filter(|name| !local.contains(name)).collect();
+    names.sort();
+    for name in names {
+        out.push_str(&format!("\n{}{}\n", SESSION_PREFIX, name));
+        for task in &data.sessions[name] {
+            out.push_str(checkbox(task.status));
+            out.push(' ');
+            out.push_str(&task.description);
+            for tag in &task.tags {
+                out.push_str(&format!(" #{}", tag));
+            }
+            if let Some(time) = task.time {
+                out.push_str(&format!(" {}{}", DUE_PREFIX, (time + TimeDelta::hours(offset_hours)).format(DUE_FORMAT)));
+            }
+            if let Some(id) = task.short_id() {
+                out.push_str(&format!(" ={}", id));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+struct Line {
+    session: String,
+    status: Status,
+    description: String,
+    tags: Vec<String>,
+    time: Option<DateTime<Utc>>,
+    id: Option<String>,
+}
+
+fn parse(text: &str, offset_hours: i64) -> Result<Vec<Line>> {
+    let mut session = None;
+    let mut lines = Vec::new();
+    for (line_no, raw) in text.lines().enumerate() {
+        let raw = raw.trim();
+        if let Some(name) = raw.strip_prefix(SESSION_PREFIX) {
+            session = Some(name.trim().to_string());
+            continue;
+        }
+        if raw.is_empty() || raw.starts_with('#') {
+            continue;
+        }
+        let context = || format!("mirror line {}: '{}'", line_no + 1, raw);
+        let session = session.clone().with_context(|| format!("{} is outside of a '{}session' section", context(), SESSION_PREFIX))?;
+        let (status, rest) = match raw.get(..3).and_then(parse_checkbox) {
+            Some(status) => (status, &raw[3..]),
+            None => (Status::Todo, raw),
+        };
+
+        // Срок и id читаются только с конца строки: "due:" и "=" внутри описания - его часть
+        let mut words: Vec<&str> = rest.split_whitespace().collect();
+        let (mut time, mut id) = (None, None);
+        while let Some(&word) = words.last() {
+            if let Some(due) = word.strip_prefix(DUE_PREFIX).filter(|d| time.is_none() && d.starts_with(|c: char| c.is_ascii_digit())) {
+                let local = NaiveDateTime::parse_from_str(due, DUE_FORMAT).with_context(|| format!("{}: expected {}YYYY-MM-DDTHH:MM", context(), DUE_PREFIX))?;
+                time = Some(local.and_utc() - TimeDelta::hours(offset_hours));
+            } else if let Some(short) = word.strip_prefix('=').filter(|s| id.is_none() && time.is_none() && !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric())) {
+                id = Some(short.to_lowercase());
+            } else {
+                break;
+            }
+            words.pop();
+        }
+        let mut tags = Vec::new();
+        let description = split_tags(&words.join(" "), &mut tags);
+        if description.is_empty() {
+            anyhow::bail!("{} has no description", context());
+        }
+        lines.push(Line { session, status, description, tags, time, id });
+    }
+    Ok(lines)
+}
+
+// Переносит правки из файла в данные: по =id обновляет, без id добавляет,
+// пропавшие из файла задачи уходят в корзину. Возвращает число изменений
+fn apply(data: &mut Data, lines: Vec<Line>, local: &[String]) -> usize {
+    let now = Utc::now();
+    let mut changes = 0;
+    let mut placed: Vec<(String, Task)> = Vec::new();
+
+    for mut line in lines {
+        let existing = line.id.as_deref().and_then(|id| {
+            data.sessions.iter().filter(|(name, _)| !local.contains(name)).find_map(|(name, tasks)| {
+                tasks.iter().position(|t| t.short_id().as_deref() == Some(id)).map(|idx| (name.clone(), idx))
+            })
+        });
+        // В сессию с sync = false из файла ничего не попадает: новая строка пропускается,
+        // перенесённая задача остаётся на старом месте
+        if local.contains(&line.session) {
+            let what = if existing.is_some() { "stays where it was" } else { "was not added" };
+            println!("Mirror: session '{}' has sync = false, '{}' {}", line.session, line.description, what);
+            match &existing {
+                Some((session, _)) => line.session = session.clone(),
+                None => continue,
+            }
+        }
+        let Some((session, idx)) = existing else {
+            changes += 1;
+            placed.push((line.session, Task {
+                description: line.description,
+                tags: line.tags,
+                time: line.time,
+                status: line.status,
+                completed_at: (line.status == Status::Done).then_some(now),
+                created_at: Some(now),
+                ..Default::default()
+            }));
+            continue;
+        };
+
+        let mut task = data.sessions.get_mut(&session).unwrap().remove(idx);
+        // В файле время с точностью до минуты, секунды не считаем правкой
+        let time_edited = task.time.map(|t| t.timestamp() / 60) != line.time.map(|t| t.timestamp() / 60);
+        let edited = time_edited || task.description != line.description || task.tags != line.tags || task.status != line.status || session != line.session;
+        if edited && task.locked {
+            println!("Mirror: '{}' is locked, edits to it were ignored", task.description);
+        } else if edited {
+            changes += 1;
+            if task.status != line.status {
+                task.completed_at = (line.status == Status::Done).then_some(now);
+            }
+            task.status = line.status;
+            task.description = line.description;
+            task.tags = line.tags;
+            if time_edited {
+                task.move_to(line.time);
+            }
+        }
+        let target = if task.locked { session } else { line.session };
+        placed.push((target, task));
+    }
+
+    // Всё, что осталось на месте, из файла удалили
+    for (name, tasks) in data.sessions.iter_mut().filter(|(name, _)| !local.contains(name)) {
+        for task in tasks.drain(..) {
+            if task.locked {
+                placed.push((name.clone(), task));
+                continue;
+            }
+            changes += 1;
+            println!("Mirror: '{}' was deleted from the file, moved to trash", task.description);
+            data.trash.push(TrashEntry {
+                session: name.clone(),
+                note: Some("deleted from mirror".to_string()),
+                task,
+                deleted_at: now,
+            });
+        }
+    }
+    for (name, task) in placed {
+        data.sessions.entry(name).or_default().push(task);
+    }
+    for tasks in data.sessions.values_mut() {
+        crate::sort_tasks(tasks);
+    }
+    changes
+}
+
+// При запуске: если файл правили руками, забираем правки. Если с последней записи
+// изменились и файл, и данные, ничего не сливаем, а откладываем файл в .conflict
+pub fn sync_in(data: &mut Data, path: &Path, local: &[String], offset_hours: i64) -> Result<()> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(());
+    };
+    let Some(written) = data.mirror_hash else {
+        return Ok(());
+    };
+    if hash(&text) == written {
+        return Ok(());
+    }
+    if hash(render(data, local, offset_hours)) != written {
+        let conflict = path.with_extension("conflict");
+        fs::write(&conflict, &text)?;
+        eprintln!(
+            "Warning: {} and the task data both changed since the last sync; your edits were saved to {} and the mirror rewritten",
+            path.display(),
+            conflict.display()
+        );
+        return Ok(());
+    }
+
+    // Ошибка в файле не должна останавливать ttd: правки откладываем, как при конфликте
+    let lines = match parse(&text, offset_hours) {
+        Ok(lines) => lines,
+        Err(err) => {
+            let rejected = path.with_extension("conflict");
+            fs::write(&rejected, &text)?;
+            eprintln!("Warning: {:#}; your edits were saved to {} and the mirror rewritten", err, rejected.display());
+            return Ok(());
+        }
+    };
+    let changes = apply(data, lines, local);
+    if changes > 0 {
+        println!("Mirror: applied {} change(s) from {}", changes, path.display());
+    }
+    Ok(())
+}
+
+// Перед сохранением запоминаем хеш того, что сейчас запишем в файл
+pub fn prepare(data: &mut Data, local: &[String], offset_hours: i64) -> String {
+    let text = render(data, local, offset_hours);
+    data.mirror_hash = Some(hash(&text));
+    text
+}
+
+pub fn write(path: &Path, text: &str) -> Result<()> {
+    if fs::read_to_string(path).is_ok_and(|old| old == text) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, text).with_context(|| format!("Failed to write mirror {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_trailing_metadata() {
+        let lines = parse("## work\n[x] buy milk #home due:2026-10-16T18:00 =K3X9\n", 3).unwrap();
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!((line.session.as_str(), line.status), ("work", Status::Done));
+        assert_eq!(line.description, "buy milk");
+        assert_eq!(line.tags, vec!["home".to_string()]);
+        assert_eq!(line.time.unwrap().to_rfc3339(), "2026-10-16T15:00:00+00:00");
+        assert_eq!(line.id.as_deref(), Some("k3x9"));
+    }
+
+    #[test]
+    fn metadata_words_inside_the_description_stay() {
+        let lines = parse("## work\n[ ] check due:diligence with =bob today\nplain line\n", 0).unwrap();
+        assert_eq!(lines[0].description, "check due:diligence with =bob today");
+        assert_eq!((lines[0].time, lines[0].id.as_deref()), (None, None));
+        assert_eq!((lines[1].status, lines[1].description.as_str()), (Status::Todo, "plain line"));
+    }
+
+    #[test]
+    fn bad_lines_are_errors() {
+        assert!(parse("[ ] no section\n", 0).is_err());
+        assert!(parse("## work\n[ ] milk due:2026-13-40T99:00\n", 0).is_err());
+        assert!(parse("## work\n[ ] =k3x9\n", 0).is_err());
+    }
+
+    #[test]
+    fn render_reads_back() {
+        let mut data = Data::default();
+        let task = Task { id: Some(26), description: "pay rent".to_string(), tags: vec!["home".to_string()], ..Default::default() };
+        data.sessions.insert("work".to_string(), vec![task]);
+        let lines = parse(&render(&data, &[], 3), 3).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!((lines[0].description.as_str(), lines[0].id.as_deref()), ("pay rent", Some("2832")));
+        assert!(parse(&render(&data, &["work".to_string()], 3), 3).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use xdg::BaseDirectories;
+
+use crate::Task;
+
+// Номера из последнего l/ll: сессия -> id задач по индексам, как их видел пользователь.
+// None - файл ещё не читали
+static SEEN: Mutex<Option<HashMap<String, Vec<u64>>>> = Mutex::new(None);
+// В пакете файл пишется только вместе с данными, после последней строки
+static DEFERRED: AtomicBool = AtomicBool::new(false);
+static PENDING: AtomicBool = AtomicBool::new(false);
+// Сессия текущей команды: по ней сообщения берут номера задач
+static SESSION: Mutex<String> = Mutex::new(String::new());
+
+fn state_path() -> Result<PathBuf> {
+    let xdg = BaseDirectories::with_prefix("ttd")?;
+    Ok(xdg.place_state_file("last_list.json")?)
+}
+
+fn load() -> HashMap<String, Vec<u64>> {
+    state_path()
+    .ok()
+    .and_then(|path| fs::read_to_string(path).ok())
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+// Запоминает порядок сессии после вывода списка
+pub fn remember(session: &str, tasks: &[Task]) -> Result<()> {
+    let ids: Vec<u64> = tasks.iter().map(|t| t.id.unwrap_or(0)).collect();
+    let mut seen = SEEN.lock().unwrap();
+    let seen = seen.get_or_insert_with(load);
+    if seen.get(session) == Some(&ids) {
+        return Ok(());
+    }
+    seen.insert(session.to_string(), ids);
+    if DEFERRED.load(Ordering::Relaxed) {
+        PENDING.store(true, Ordering::Relaxed);
+        return Ok(());
+    }
+    fs::write(state_path()?, serde_json::to_string(seen)?)?;
+    Ok(())
+}
+
+pub fn defer() {
+    DEFERRED.store(true, Ordering::Relaxed);
+}
+
+// Пишет отложенный порядок; вызывается после сохранения данных
+pub fn flush() -> Result<()> {
+    if !PENDING.swap(false, Ordering::Relaxed) {
+        return Ok(());
+    }
+    let seen = SEEN.lock().unwrap();
+    if let Some(seen) = seen.as_ref() {
+        fs::write(state_path()?, serde_json::to_string(seen)?)?;
+    }
+    Ok(())
+}
+
+// Индекс из последнего списка этой сессии -> текущая позиция задачи.
+// Some(None) - такого номера в списке нет или задачу с тех пор удалили; None - списка нет, индекс как есть.
+// Со списком живые индексы не используются: иначе два номера могут указать на одну задачу
+pub fn resolve(session: &str, tasks: &[Task], index: usize) -> Option<Option<usize>> {
+    let mut seen = SEEN.lock().unwrap();
+    resolve_in(seen.get_or_insert_with(load), session, tasks, index)
+}
+
+fn resolve_in(seen: &HashMap<String, Vec<u64>>, session: &str, tasks: &[Task], index: usize) -> Option<Option<usize>> {
+    let ids = seen.get(session)?;
+    Some(ids.get(index).and_then(|&id| tasks.iter().position(|t| t.id == Some(id))))
+}
+
+pub fn set_session(session: &str) {
+    *SESSION.lock().unwrap() = session.to_string();
+}
+
+// Номер задачи для сообщений: как в последнем списке текущей сессии, иначе её позиция сейчас
+pub fn shown(task: &Task, index: usize) -> usize {
+    let mut seen = SEEN.lock().unwrap();
+    let seen = seen.get_or_insert_with(load);
+    seen.get(SESSION.lock().unwrap().as_str())
+    .and_then(|ids| ids.iter().position(|&id| task.id == Some(id)))
+    .unwrap_or(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(ids: &[u64]) -> Vec<Task> {
+        ids.iter().map(|&id| Task { id: Some(id), ..Default::default() }).collect()
+    }
+
+    #[test]
+    fn follows_the_listed_order() {
+        let seen = HashMap::from([("work".to_string(), vec![7, 3, 5])]);
+        // С тех пор порядок поменялся: #0 из списка - задача 7, теперь она на месте 2
+        let now = tasks(&[3, 5, 7]);
+        assert_eq!(resolve_in(&seen, "work", &now, 0), Some(Some(2)));
+        assert_eq!(resolve_in(&seen, "work", &now, 1), Some(Some(0)));
+    }
+
+    #[test]
+    fn missing_numbers_do_not_fall_back_to_positions() {
+        let seen = HashMap::from([("work".to_string(), vec![7, 3])]);
+        let now = tasks(&[3, 5, 7]);
+        // Номера 2 в списке не было, хотя задача на месте 2 есть
+        assert_eq!(resolve_in(&seen, "work", &now, 2), Some(None));
+        // Задачу 7 удалили
+        assert_eq!(resolve_in(&seen, "work", &tasks(&[3]), 0), Some(None));
+    }
+
+    #[test]
+    fn without_a_list_the_index_is_used_as_is() {
+        let seen = HashMap::from([("work".to_string(), vec![7])]);
+        assert_eq!(resolve_in(&seen, "home", &tasks(&[1, 2]), 1), None);
+    }
+}
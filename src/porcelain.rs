@@ -0,0 +1,50 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{Status, Task};
+
+// Формат для плагинов редакторов. Меняется только вместе с VERSION,
+// новые поля добавляются в конец строки, старые не переставляются
+pub const VERSION: u32 = 1;
+
+pub fn print_header() {
+    println!("ttd-porcelain\t{}", VERSION);
+}
+
+// session <TAB> имя <TAB> текущая (0/1) <TAB> выполнено <TAB> всего
+pub fn print_session(name: &str, tasks: &[Task], is_current: bool) {
+    let completed = tasks.iter().filter(|t| !t.is_open()).count();
+    println!("session\t{}\t{}\t{}\t{}", escape(name), u8::from(is_current), completed, tasks.len());
+}
+
+// task <TAB> сессия <TAB> индекс <TAB> статус <TAB> срок <TAB> план <TAB> описание <TAB> конец <TAB> полный статус.
+// Время в UTC (RFC 3339), '-' если не задано. Четвёртое поле осталось трёхзначным
+// для старых плагинов, подробный статус добавлен в конец
+pub fn print_task(session: &str, index: usize, task: &Task) {
+    let (status, full_status) = match task.effective_status() {
+        Status::Todo => ("todo", "todo"),
+        Status::InProgress => ("todo", "in_progress"),
+        Status::Waiting => ("waiting", "waiting"),
+        Status::Done => ("done", "done"),
+        Status::Cancelled => ("done", "cancelled"),
+    };
+    println!(
+        "task\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        escape(session),
+        index,
+        status,
+        timestamp(task.time),
+        timestamp(task.scheduled),
+        escape(&task.description),
+        timestamp(task.end),
+        full_status,
+        task.short_id().unwrap_or_else(|| "-".to_string())
+    );
+}
+
+fn timestamp(time: Option<DateTime<Utc>>) -> String {
+    time.map_or("-".to_string(), |t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Data;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Фаза -> (суммарное время, число вызовов), в порядке первого появления
+static PHASES: Mutex<Vec<(&'static str, Duration, u32)>> = Mutex::new(Vec::new());
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn record(phase: &'static str, elapsed: Duration) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut phases = PHASES.lock().unwrap();
+    match phases.iter_mut().find(|(name, _, _)| *name == phase) {
+        Some((_, total, calls)) => {
+            *total += elapsed;
+            *calls += 1;
+        }
+        None => phases.push((phase, elapsed, 1)),
+    }
+}
+
+pub fn time<T>(phase: &'static str, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let started = Instant::now();
+    let result = f();
+    record(phase, started.elapsed());
+    result
+}
+
+// Пишем в stderr, чтобы не мешать выводу команды и --porcelain
+pub fn report(data: &Data, file_size: u64, total: Duration) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let tasks: usize = data.sessions.values().map(Vec::len).sum();
+    eprintln!(
+        "Profile: {} sessions, {} tasks, {} in trash, {:.1} KiB on disk",
+        data.sessions.len(),
+        tasks,
+        data.trash.len(),
+        file_size as f64 / 1024.0
+    );
+    for (phase, elapsed, calls) in PHASES.lock().unwrap().iter() {
+        let calls = if *calls > 1 { format!(" ({} calls)", calls) } else { String::new() };
+        eprintln!("  {:<14}{:>10.3} ms{}", phase, elapsed.as_secs_f64() * 1000.0, calls);
+    }
+    eprintln!("  {:<14}{:>10.3} ms", "total", total.as_secs_f64() * 1000.0);
+    eprintln!("  (match and sort are nested in the phases that call them)");
+}
@@ -0,0 +1,272 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, FixedOffset, Months, NaiveDate, TimeDelta, Utc, Weekday};
+
+// Правило повторения хранится в задаче строкой ("1d", "2w", "mon,thu", "0 9 * * MON-FRI", "year 1990-05-12")
+// и разбирается при каждом использовании
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    Hours(i64),
+    Days(i64),
+    Weeks(i64),
+    Months(u32),
+    Weekdays(Vec<Weekday>),
+    // Выражение cron из пяти полей, время в нём местное
+    Cron(String),
+    // Годовщина: всегда в исходный месяц и день, а не через год от прошлого раза.
+    // Без даты берётся дата срока (её подставляет add), с годом показывается "turns N"
+    Yearly { date: Option<(u32, u32)>, since: Option<i32> },
+}
+
+impl Rule {
+    // "1d", "12h", "2w", "1M", "day", "week", "month", "weekday", "mon", "mon,wed,fri"
+    pub fn parse(input: &str) -> Result<Rule> {
+        let input = input.trim();
+        match input.to_lowercase().as_str() {
+            "hour" => return Ok(Rule::Hours(1)),
+            "day" => return Ok(Rule::Days(1)),
+            "week" => return Ok(Rule::Weeks(1)),
+            "month" => return Ok(Rule::Months(1)),
+            "year" => return Ok(Rule::Yearly { date: None, since: None }),
+            "weekday" => return Ok(Rule::Weekdays(vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri])),
+            _ => {}
+        }
+
+        if let Some(date) = input.strip_prefix("year ") {
+            return parse_anniversary(date.trim());
+        }
+
+        if input.split_whitespace().count() == 5 {
+            parse_cron(input)?;
+            return Ok(Rule::Cron(input.split_whitespace().collect::<Vec<_>>().join(" ")));
+        }
+
+        if let Some(days) = input.split(',').map(parse_weekday).collect::<Option<Vec<_>>>() {
+            return Ok(Rule::Weekdays(days));
+        }
+
+        let digits = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+        let (count, unit) = input.split_at(digits);
+        let count: u32 = count.parse().unwrap_or(0);
+        if count == 0 {
+            anyhow::bail!("Unknown repeat rule '{}'. Use e.g. 1d, 12h, 2w, 1M, weekday, mon,thu, year 05-12 or '0 9 * * MON-FRI'", input);
+        }
+        match unit {
+            "h" => Ok(Rule::Hours(count.into())),
+            "d" => Ok(Rule::Days(count.into())),
+            "w" => Ok(Rule::Weeks(count.into())),
+            "M" => Ok(Rule::Months(count)),
+            _ => anyhow::bail!("Unknown repeat unit '{}'. Use h, d, w or M (months)", unit),
+        }
+    }
+
+    // Следующее повторение после from, которое уже в будущем относительно now.
+    // Пропущенные повторения не накапливаются
+    pub fn next(&self, from: DateTime<Utc>, now: DateTime<Utc>, offset_hours: i64) -> DateTime<Utc> {
+        let step = match self {
+            Rule::Hours(n) => TimeDelta::hours(*n),
+            Rule::Days(n) => TimeDelta::days(*n),
+            Rule::Weeks(n) => TimeDelta::weeks(*n),
+            Rule::Months(n) => {
+                let add = |t: DateTime<Utc>| t.checked_add_months(Months::new(*n)).unwrap_or(DateTime::<Utc>::MAX_UTC);
+                let mut next = add(from);
+                while next <= now {
+                    next = add(next);
+                }
+                return next;
+            }
+            Rule::Weekdays(days) => {
+                // Перепрыгиваем целые дни до "сегодня", дальше ищем ближайший подходящий день
+                let mut next = from + TimeDelta::days((now - from).num_days().max(0));
+                loop {
+                    next += TimeDelta::days(1);
+                    let weekday = (next + TimeDelta::hours(offset_hours)).weekday();
+                    if next > now && days.contains(&weekday) {
+                        return next;
+                    }
+                }
+            }
+            Rule::Cron(expr) => {
+                let zone = FixedOffset::east_opt((offset_hours * 3600) as i32).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+                let start = from.max(now).with_timezone(&zone);
+                return parse_cron(expr)
+                .ok()
+                .and_then(|cron| cron.find_next_occurrence(&start, false).ok())
+                .map_or(DateTime::<Utc>::MAX_UTC, |next| next.with_timezone(&Utc));
+            }
+            Rule::Yearly { date, .. } => {
+                let offset = TimeDelta::hours(offset_hours);
+                let local = from + offset;
+                let (month, day) = date.unwrap_or((local.month(), local.day()));
+                let after = from.max(now);
+                let mut year = (after + offset).year();
+                loop {
+                    let next = anniversary(year, month, day).and_time(local.time()).and_utc() - offset;
+                    if next > after {
+                        return next;
+                    }
+                    year += 1;
+                }
+            }
+        };
+
+        let skipped = if from < now { (now - from).num_seconds() / step.num_seconds() } else { 0 };
+        let next = from + step * (skipped as i32 + 1);
+        if next <= now { next + step } else { next }
+    }
+
+    // Первый раз для задачи без срока: у cron и годовщины он задан самим правилом
+    pub fn first(&self, now: DateTime<Utc>, offset_hours: i64) -> Option<DateTime<Utc>> {
+        match self {
+            Rule::Cron(_) => Some(self.next(now, now, offset_hours)),
+            Rule::Yearly { date: Some((month, day)), .. } => {
+                // В полночь по местному времени, как "at 12d"
+                let offset = TimeDelta::hours(offset_hours);
+                let today = (now + offset).date_naive();
+                let this_year = anniversary(today.year(), *month, *day);
+                let date = if this_year >= today { this_year } else { anniversary(today.year() + 1, *month, *day) };
+                Some(date.and_hms_opt(0, 0, 0).unwrap().and_utc() - offset)
+            }
+            _ => None,
+        }
+    }
+
+    // "every year" закрепляется за датой первого срока, чтобы 29 февраля не уехало на 28-е навсегда
+    pub fn anchored(&self, time: DateTime<Utc>, offset_hours: i64) -> Option<String> {
+        match self {
+            Rule::Yearly { date: None, .. } => Some((time + TimeDelta::hours(offset_hours)).format("year %m-%d").to_string()),
+            _ => None,
+        }
+    }
+
+    // Сколько исполняется в повторение at: "turns 36"
+    pub fn turns(&self, at: DateTime<Utc>, offset_hours: i64) -> Option<i32> {
+        match self {
+            Rule::Yearly { since: Some(since), .. } => Some((at + TimeDelta::hours(offset_hours)).year() - since),
+            _ => None,
+        }
+    }
+
+    // RRULE для ICS (RFC 5545). Cron через RRULE в общем виде не выражается,
+    // такие задачи уходят в календарь без повторения
+    pub fn to_rrule(&self) -> Option<String> {
+        Some(match self {
+            Rule::Hours(n) => format!("FREQ=HOURLY;INTERVAL={}", n),
+            Rule::Days(n) => format!("FREQ=DAILY;INTERVAL={}", n),
+            Rule::Weeks(n) => format!("FREQ=WEEKLY;INTERVAL={}", n),
+            Rule::Months(n) => format!("FREQ=MONTHLY;INTERVAL={}", n),
+            Rule::Weekdays(days) => {
+                let days: Vec<&str> = days.iter().map(|d| ICS_DAYS[d.num_days_from_monday() as usize]).collect();
+                format!("FREQ=WEEKLY;BYDAY={}", days.join(","))
+            }
+            // BYMONTHDAY=-1 - последний день февраля, как и у нас для 29-го
+            Rule::Yearly { date: Some((2, 29)), .. } => "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1".to_string(),
+            Rule::Yearly { date: Some((month, day)), .. } => format!("FREQ=YEARLY;BYMONTH={};BYMONTHDAY={}", month, day),
+            Rule::Yearly { date: None, .. } => "FREQ=YEARLY".to_string(),
+            Rule::Cron(_) => return None,
+        })
+    }
+}
+
+// "05-12" или "1990-05-12"; 29 февраля разрешено и без года
+fn parse_anniversary(text: &str) -> Result<Rule> {
+    let usage = || anyhow::anyhow!("Unknown date '{}' for a yearly rule. Use e.g. year 05-12 or year 1990-05-12", text);
+    let (since, month_day) = match text.split_once('-').filter(|(year, _)| year.len() == 4) {
+        Some((year, rest)) => (Some(year.parse::<i32>().map_err(|_| usage())?), rest),
+        None => (None, text),
+    };
+    let (month, day) = month_day.split_once('-').ok_or_else(usage)?;
+    let (month, day): (u32, u32) = (month.parse().map_err(|_| usage())?, day.parse().map_err(|_| usage())?);
+    // Без года проверяем по високосному 2000-му, чтобы 02-29 проходило
+    if NaiveDate::from_ymd_opt(since.unwrap_or(2000), month, day).is_none() {
+        return Err(usage());
+    }
+    Ok(Rule::Yearly { date: Some((month, day)), since })
+}
+
+// Дата годовщины в году year: 29 февраля в невисокосный год празднуется 28-го
+fn anniversary(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day)
+    .or_else(|| NaiveDate::from_ymd_opt(year, month, day - 1))
+    .unwrap_or(NaiveDate::MAX)
+}
+
+fn parse_cron(expr: &str) -> Result<croner::Cron> {
+    croner::Cron::new(expr)
+    .parse()
+    .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", expr, e))
+}
+
+const ICS_DAYS: [&str; 7] = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    match name.trim().to_lowercase().as_str() {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(Rule::parse("2w").unwrap(), Rule::Weeks(2));
+        assert_eq!(Rule::parse("mon,thu").unwrap(), Rule::Weekdays(vec![Weekday::Mon, Weekday::Thu]));
+        assert_eq!(Rule::parse("0  9 * * MON-FRI").unwrap(), Rule::Cron("0 9 * * MON-FRI".to_string()));
+        assert_eq!(Rule::parse("year 02-29").unwrap(), Rule::Yearly { date: Some((2, 29)), since: None });
+        assert!(Rule::parse("year 1991-02-29").is_err());
+        assert!(Rule::parse("0 99 * * *").is_err());
+        assert!(Rule::parse("3x").is_err());
+    }
+
+    #[test]
+    fn missed_occurrences_do_not_pile_up() {
+        let next = Rule::Days(1).next(at(2026, 1, 1, 9), at(2026, 1, 5, 12), 0);
+        assert_eq!(next, at(2026, 1, 6, 9));
+        // Срок ещё впереди: просто следующий шаг
+        assert_eq!(Rule::Hours(12).next(at(2026, 1, 5, 9), at(2026, 1, 1, 0), 0), at(2026, 1, 5, 21));
+    }
+
+    #[test]
+    fn months_clamp_to_the_end_of_month() {
+        assert_eq!(Rule::Months(1).next(at(2026, 1, 31, 9), at(2026, 1, 31, 9), 0), at(2026, 2, 28, 9));
+    }
+
+    #[test]
+    fn weekdays_use_local_time() {
+        // 2026-10-16 - пятница; 22:00 UTC в четверг при +3 - уже пятница, суббота наступит через сутки
+        let rule = Rule::parse("mon").unwrap();
+        assert_eq!(rule.next(at(2026, 10, 16, 6), at(2026, 10, 16, 6), 3), at(2026, 10, 19, 6));
+        let rule = Rule::parse("sat").unwrap();
+        assert_eq!(rule.next(at(2026, 10, 15, 22), at(2026, 10, 15, 22), 3), at(2026, 10, 16, 22));
+    }
+
+    #[test]
+    fn cron_is_local_time() {
+        let rule = Rule::parse("0 9 * * MON-FRI").unwrap();
+        // Пятница, 10:00 по +3: следующий раз в понедельник в 9:00 по +3
+        assert_eq!(rule.next(at(2026, 10, 16, 7), at(2026, 10, 16, 7), 3), at(2026, 10, 19, 6));
+        assert_eq!(rule.first(at(2026, 10, 19, 5), 3), Some(at(2026, 10, 19, 6)));
+    }
+
+    #[test]
+    fn february_29_comes_back_in_leap_years() {
+        let rule = Rule::parse("year 1996-02-29").unwrap();
+        assert_eq!(rule.next(at(2028, 2, 29, 0), at(2028, 2, 29, 0), 0), at(2029, 2, 28, 0));
+        assert_eq!(rule.next(at(2031, 2, 28, 0), at(2031, 2, 28, 0), 0), at(2032, 2, 29, 0));
+        assert_eq!(rule.turns(at(2032, 2, 29, 0), 0), Some(36));
+        assert_eq!(rule.to_rrule().as_deref(), Some("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1"));
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::{Datelike, TimeDelta, Utc};
+
+use crate::accessibility::{describe_due, describe_span};
+use crate::recurrence::Rule;
+use crate::{format_delta, format_minutes, format_range, format_time, turns_label, Data, Status, Task, TimeSettings};
+
+// Разбор просрочек: что сделано с опозданием и что всё ещё просрочено
+pub fn overdue_report(data: &Data, offset_hours: i64) {
+    let now = Utc::now();
+    let mut names: Vec<&String> = data.sessions.keys().collect();
+    names.sort();
+
+    let mut late_count: i32 = 0;
+    let mut overdue_count: i32 = 0;
+    let mut total_late = TimeDelta::zero();
+
+    for name in names {
+        let mut lines = Vec::new();
+
+        for task in &data.sessions[name] {
+            let Some(due) = task.deadline() else {
+                continue;
+            };
+            let due_str = format_time(&Some(due), offset_hours);
+
+            if task.is_open() && due < now {
+                let late = now - due;
+                overdue_count += 1;
+                total_late += late;
+                lines.push((late, format!("  still overdue by {}: {} (due {})", describe_span(late), task.description, due_str)));
+            } else if let Some(completed) = task.completed_at.filter(|&c| task.status == Status::Done && c > due) {
+                let late = completed - due;
+                late_count += 1;
+                total_late += late;
+                lines.push((late, format!(
+                    "  done {} late: {} (due {}, done {})",
+                    describe_span(late),
+                    task.description,
+                    due_str,
+                    format_time(&task.completed_at, offset_hours)
+                )));
+            }
+        }
+
+        if lines.is_empty() {
+            continue;
+        }
+        // Сначала самые сильные опоздания
+        lines.sort_by_key(|(late, _)| std::cmp::Reverse(*late));
+        println!("{}:", name);
+        for (_, line) in lines {
+            println!("{}", line);
+        }
+        println!();
+    }
+
+    let total = late_count + overdue_count;
+    if total == 0 {
+        println!("Nothing is overdue and nothing was completed late");
+    } else {
+        println!(
+            "{} completed late, {} still overdue, average lateness {}",
+            late_count,
+            overdue_count,
+            describe_span(total_late / total)
+        );
+    }
+}
+
+// Сколько задач показывать в каждом разделе present
+const PRESENT_LIMIT: usize = 5;
+
+// Сводка для показа на созвоне: без цветов, крупными блоками с отступами
+pub fn present(name: &str, tasks: &[Task], offset_hours: i64) {
+    let now = Utc::now();
+    let done = tasks.iter().filter(|t| !t.is_open()).count();
+    let percent = if tasks.is_empty() { 0 } else { done * 100 / tasks.len() };
+
+    println!();
+    println!("  {}", name.to_uppercase());
+    println!();
+    println!("  {} of {} done ({}%)", done, tasks.len(), percent);
+
+    let mut overdue: Vec<&Task> = tasks.iter().filter(|t| t.is_open() && t.deadline().is_some_and(|d| d < now)).collect();
+    overdue.sort_by_key(|t| t.deadline());
+    let mut upcoming: Vec<&Task> = tasks.iter().filter(|t| t.is_open() && t.time.is_some_and(|d| d >= now)).collect();
+    upcoming.sort_by_key(|t| t.time);
+    let waiting: Vec<&Task> = tasks.iter().filter(|t| t.is_open() && t.delegate.is_some()).collect();
+
+    let section = |title: &str, items: &[&Task], describe: &dyn Fn(&Task) -> String| {
+        if items.is_empty() {
+            return;
+        }
+        println!();
+        println!("  {} ({})", title, items.len());
+        println!();
+        for task in items.iter().take(PRESENT_LIMIT) {
+            println!("    - {}  {}", task.description, describe(task));
+        }
+        if items.len() > PRESENT_LIMIT {
+            println!("    ... and {} more", items.len() - PRESENT_LIMIT);
+        }
+    };
+
+    section("OVERDUE", &overdue, &|t| describe_due(t.deadline(), now));
+    section("NEXT", &upcoming, &|t| format_range(t, offset_hours).trim_matches(['[', ']']).to_string());
+    section("WAITING ON OTHERS", &waiting, &|t| t.delegate.clone().unwrap_or_default());
+
+    if overdue.is_empty() && upcoming.is_empty() && waiting.is_empty() {
+        println!();
+        println!("  Nothing due and nothing overdue");
+    }
+    println!();
+}
+
+// Сумма оценок открытых задач: на сегодня (вместе с просроченными), до конца недели и всего
+pub fn load(data: &Data, time: &TimeSettings) {
+    let offset = TimeDelta::hours(time.offset_hours);
+    let today = (Utc::now() + offset).date_naive();
+    let week_end = today + TimeDelta::days(6 - i64::from(today.weekday().days_since(time.week_start)));
+
+    let mut names: Vec<&String> = data.sessions.keys().collect();
+    names.sort();
+
+    let mut totals = [0u32; 3];
+    let mut unestimated = 0;
+    let mut rows = Vec::new();
+    for name in names {
+        let mut sums = [0u32; 3];
+        for task in data.sessions[name].iter().filter(|t| t.is_open()) {
+            let due = task.deadline().map(|d| (d + offset).date_naive());
+            let Some(minutes) = task.estimate else {
+                unestimated += usize::from(due.is_some_and(|d| d <= week_end));
+                continue;
+            };
+            sums[0] += if due.is_some_and(|d| d <= today) { minutes } else { 0 };
+            sums[1] += if due.is_some_and(|d| d <= week_end) { minutes } else { 0 };
+            sums[2] += minutes;
+        }
+        if sums[2] > 0 {
+            for (total, sum) in totals.iter_mut().zip(sums) {
+                *total += sum;
+            }
+            rows.push((name.as_str(), sums));
+        }
+    }
+
+    if rows.is_empty() {
+        println!("No estimates yet, add one with 'ttd est <task> 90m'");
+        return;
+    }
+
+    let width = rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0).max("total".len());
+    let show = |minutes: u32| if minutes == 0 { "-".to_string() } else { format_minutes(minutes) };
+    println!("{:<width$}  {:>8}  {:>9}  {:>8}", "", "today", "this week", "open", width = width);
+    for (name, sums) in &rows {
+        println!("{:<width$}  {:>8}  {:>9}  {:>8}", name, show(sums[0]), show(sums[1]), show(sums[2]), width = width);
+    }
+    if rows.len() > 1 {
+        println!("{:<width$}  {:>8}  {:>9}  {:>8}", "total", show(totals[0]), show(totals[1]), show(totals[2]), width = width);
+    }
+    if unestimated > 0 {
+        println!("{} tasks due this week have no estimate", unestimated);
+    }
+}
+
+// Сколько задач в каком состоянии по сессиям
+pub fn stats(data: &Data) {
+    let now = Utc::now();
+    let mut rows = Vec::new();
+    for (name, tasks) in &data.sessions {
+        let count = |status: Status| tasks.iter().filter(|t| t.status == status).count();
+        let open = tasks.iter().filter(|t| t.is_open()).count();
+        let overdue = tasks.iter().filter(|t| t.is_open() && t.deadline().is_some_and(|d| d < now)).count();
+        rows.push((name.as_str(), [open, overdue, count(Status::Done), count(Status::Cancelled)]));
+    }
+    if rows.is_empty() {
+        println!("No sessions yet");
+        return;
+    }
+
+    let width = rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0).max("total".len());
+    println!("{:<width$}  {:>5}  {:>7}  {:>5}  {:>9}", "", "open", "overdue", "done", "cancelled", width = width);
+    let mut totals = [0; 4];
+    for (name, counts) in &rows {
+        println!("{:<width$}  {:>5}  {:>7}  {:>5}  {:>9}", name, counts[0], counts[1], counts[2], counts[3], width = width);
+        for (total, count) in totals.iter_mut().zip(counts) {
+            *total += count;
+        }
+    }
+    if rows.len() > 1 {
+        println!("{:<width$}  {:>5}  {:>7}  {:>5}  {:>9}", "total", totals[0], totals[1], totals[2], totals[3], width = width);
+    }
+}
+
+// Сроки на ближайшие дни: повторяющиеся задачи разворачиваются во все будущие разы
+pub fn forecast(data: &Data, offset_hours: i64, span: TimeDelta) {
+    let now = Utc::now();
+    let until = now + span;
+    let offset = TimeDelta::hours(offset_hours);
+
+    let mut items = Vec::new();
+    for (name, tasks) in &data.sessions {
+        for task in tasks.iter().filter(|t| t.is_open()) {
+            let Some(time) = task.time else { continue };
+            let rule = task.recurrence.as_deref().and_then(|r| Rule::parse(r).ok());
+            let mut next = match &rule {
+                Some(rule) if time < now => rule.next(time, now, offset_hours),
+                _ => time,
+            };
+            while next < until {
+                if next >= now {
+                    items.push((next, name.as_str(), task));
+                }
+                match &rule {
+                    Some(rule) => next = rule.next(next, next, offset_hours),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    if items.is_empty() {
+        println!("Nothing due in the next {}", format_delta(span));
+        return;
+    }
+    items.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+
+    let mut day = None;
+    for (time, session, task) in &items {
+        let local = *time + offset;
+        if day != Some(local.date_naive()) {
+            day = Some(local.date_naive());
+            let minutes: u32 = items.iter()
+            .filter(|item| (item.0 + offset).date_naive() == local.date_naive())
+            .filter_map(|item| item.2.estimate)
+            .sum();
+            let load = if minutes > 0 { format!(" ({})", format_minutes(minutes)) } else { String::new() };
+            println!("{}{}", local.format("%a %Y-%m-%d"), load);
+        }
+        // Годовщина показывает возраст в этот раз, а не в ближайший
+        let every = task.recurrence.as_deref().map(|r| format!(" [every {}{}]", r, turns_label(r, Some(*time), offset_hours))).unwrap_or_default();
+        println!("  {}  {} ({}){}", local.format("%H:%M"), task.description, session, every);
+    }
+}
+
+// Запуск без команды: сессия, просрочки и ближайший срок в две строки
+pub fn summary(name: &str, tasks: &[Task], offset_hours: i64) {
+    let now = Utc::now();
+    let open = tasks.iter().filter(|t| t.is_open()).count();
+    let overdue = tasks.iter().filter(|t| t.is_open() && t.deadline().is_some_and(|d| d < now)).count();
+
+    let mut line = format!("Session '{}': {} open", name, open);
+    if overdue > 0 {
+        line.push_str(&format!(", {} overdue", overdue));
+    }
+    println!("{}", line);
+
+    let next = tasks.iter().filter(|t| t.is_open() && t.deadline().is_some_and(|d| d >= now)).min_by_key(|t| t.deadline());
+    match next {
+        Some(task) => println!(
+            "Next: {} {} ({})",
+            task.description,
+            format_range(task, offset_hours).trim_matches(['[', ']']),
+            describe_due(task.deadline(), now)
+        ),
+        None => println!("Nothing due next"),
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use strsim::jaro_winkler;
+
+use crate::{format_time, numbering, tags_label, Data, Task};
+
+pub enum Matcher {
+    Text(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(query: &str, regex: bool) -> Result<Matcher> {
+        if !regex {
+            return Ok(Matcher::Text(query.to_lowercase()));
+        }
+        let regex = RegexBuilder::new(query).case_insensitive(true).build()
+        .with_context(|| format!("'{}' is not a valid regular expression", query))?;
+        Ok(Matcher::Regex(regex))
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Text(query) => text.to_lowercase().contains(query),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+// Где нашлось: в названии ничего не пишем, остальное поясняем
+fn find_in(task: &Task, matcher: &Matcher, threshold: f64) -> Option<String> {
+    if matcher.is_match(&task.description) {
+        return Some(String::new());
+    }
+    if task.tags.iter().any(|t| matcher.is_match(t)) {
+        return Some(" (tag)".to_string());
+    }
+    if task.details.as_deref().is_some_and(|d| matcher.is_match(d)) {
+        return Some(" (note)".to_string());
+    }
+    if let Some(sub) = task.subtasks.iter().find(|s| matcher.is_match(&s.description)) {
+        return Some(format!(" (subtask '{}')", sub.description));
+    }
+    // Нечёткое совпадение только для обычного текста: у регулярки опечаток не бывает
+    let Matcher::Text(query) = matcher else {
+        return None;
+    };
+    let score = jaro_winkler(&task.description.to_lowercase(), query);
+    (score >= threshold).then(|| format!(" (~{:.0}%)", score * 100.0))
+}
+
+// Все сессии, текущая первой, остальные по имени
+pub fn print(data: &Data, current: &str, matcher: &Matcher, threshold: f64, offset_hours: i64) -> Result<()> {
+    let mut names: Vec<&String> = data.sessions.keys().collect();
+    names.sort_by_key(|name| (*name != current, name.to_lowercase()));
+
+    let (mut found, mut sessions) = (0, 0);
+    for name in names {
+        let tasks = &data.sessions[name];
+        let hits: Vec<(usize, String)> = tasks.iter().enumerate()
+        .filter_map(|(i, t)| find_in(t, matcher, threshold).map(|why| (i, why)))
+        .collect();
+        if hits.is_empty() {
+            continue;
+        }
+        // Номера из поиска работают так же, как после l
+        numbering::remember(name, tasks)?;
+        println!("{}{}", name, if name == current { " (current)" } else { "" });
+        for (i, why) in &hits {
+            let task = &tasks[*i];
+            let tags = if task.tags.is_empty() { String::new() } else { format!(" {}", tags_label(&task.tags)) };
+            println!("  #{:<3}{} {} {}{}{}", i, task.status.tag(), format_time(&task.time, offset_hours), task.description, tags, why);
+        }
+        found += hits.len();
+        sessions += 1;
+    }
+
+    match (found, sessions) {
+        (0, _) => println!("Nothing matches"),
+        (1, _) => println!("1 match"),
+        (_, 1) => println!("{} matches", found),
+        _ => println!("{} matches in {} sessions", found, sessions),
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// Команда и то, что должно быть в её выводе; пустой список - только успешный выход
+struct Check {
+    name: &'static str,
+    args: &'static [&'static str],
+    expect: &'static [&'static str],
+}
+
+const CHECKS: &[Check] = &[
+    Check { name: "switch session", args: &["s", "demo"], expect: &["Switched to session 'demo'"] },
+    Check { name: "add", args: &["a", "write report"], expect: &["Added new task 'write report'"] },
+    Check { name: "add with time", args: &["a", "buy milk", "in", "30m"], expect: &["Added new task 'buy milk'"] },
+    Check { name: "add inline time", args: &["a", "call mom in 3h"], expect: &["Added new task 'call mom'"] },
+    Check { name: "change time", args: &["t", "write report", "in", "1h"], expect: &["Changed time for 'write report'"] },
+    Check {
+        name: "sort by time",
+        args: &["l"],
+        expect: &["Task 0, todo, buy milk", "Task 1, todo, write report", "Task 2, todo, call mom"],
+    },
+    Check { name: "fuzzy match", args: &["d", "wrte report"], expect: &["Marked #1 'write report' as done"] },
+    Check { name: "undone by index", args: &["ud", "1"], expect: &["Marked #1 'write report' as NOT done"] },
+    Check { name: "remove", args: &["r", "call mom"], expect: &["Removed task #2 'call mom'"] },
+    Check { name: "summary", args: &["l"], expect: &["Session demo, current, 0 of 2 done"] },
+    Check { name: "data file", args: &["fsck"], expect: &["No problems found"] },
+];
+
+// Гоняет тот же бинарник в отдельном каталоге: настоящие задачи и конфиг не трогаются
+pub fn run() -> Result<()> {
+    let exe = std::env::current_exe().context("Cannot find the ttd executable")?;
+    let dir = std::env::temp_dir().join(format!("ttd-selftest-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let mut failed = 0;
+    for check in CHECKS {
+        match run_check(&exe, &dir, check) {
+            Ok(()) => println!("ok    {}", check.name),
+            Err(e) => {
+                failed += 1;
+                println!("FAIL  {}: {:#}", check.name, e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed, the sandbox is kept in {}", failed, CHECKS.len(), dir.display());
+    }
+    fs::remove_dir_all(&dir)?;
+    println!("All {} checks passed", CHECKS.len());
+    Ok(())
+}
+
+fn run_check(exe: &Path, dir: &Path, check: &Check) -> Result<()> {
+    let output = Command::new(exe)
+    .args(["--plain", "--no-interactive"])
+    .args(check.args)
+    .current_dir(dir)
+    .env("HOME", dir)
+    .env("XDG_CONFIG_HOME", dir.join("config"))
+    .env("XDG_STATE_HOME", dir.join("state"))
+    .env("XDG_DATA_HOME", dir.join("data"))
+    .stdin(Stdio::null())
+    .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        anyhow::bail!("'ttd {}' exited with {}\n{}{}", check.args.join(" "), output.status, stdout, String::from_utf8_lossy(&output.stderr));
+    }
+    for expected in check.expect {
+        if !stdout.contains(expected) {
+            anyhow::bail!("'ttd {}' did not print \"{}\", got:\n{}", check.args.join(" "), expected, stdout);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::export::{self, Format, Selection};
+
+// Календари подписываются на URL и опрашивают его сами; каждый запрос
+// перечитывает tasks.json, так что лента всегда совпадает с данными
+pub fn serve_ics(bind: &str, selection: &Selection, current: &str, offset_hours: i64) -> Result<()> {
+    let listener = TcpListener::bind(bind).with_context(|| format!("Failed to listen on {}", bind))?;
+    println!("Serving ICS at http://{}/tasks.ics, Ctrl-C stops", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if let Err(e) = respond(stream, selection, current, offset_hours) {
+            eprintln!("Warning: request failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, selection: &Selection, current: &str, offset_hours: i64) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Заголовки не нужны, но их надо дочитать до пустой строки
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut words = request_line.split_whitespace();
+    let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+    if method != "GET" && method != "HEAD" {
+        return write_response(&mut stream, "405 Method Not Allowed", "text/plain", "Only GET is supported\n", false);
+    }
+    if path != "/" && !path.ends_with(".ics") {
+        return write_response(&mut stream, "404 Not Found", "text/plain", "Try /tasks.ics\n", false);
+    }
+
+    let body = crate::load_data().and_then(|data| {
+        let tasks = export::select(&data, current, selection);
+        export::render(Format::Ics, &tasks, offset_hours)
+    });
+    match body {
+        Ok(body) => write_response(&mut stream, "200 OK", "text/calendar; charset=utf-8", &body, method == "HEAD"),
+        Err(e) => write_response(&mut stream, "500 Internal Server Error", "text/plain", &format!("{}\n", e), false),
+    }
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &str, head_only: bool) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if !head_only {
+        stream.write_all(body.as_bytes())?;
+    }
+    stream.flush()?;
+    Ok(())
+}
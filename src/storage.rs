@@ -0,0 +1,59 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Read;
+use std::sync::OnceLock;
+
+// Первые байты любого zstd-кадра, по ним узнаём сжатый файл при чтении
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const LEVEL: i32 = 3;
+
+#[derive(Clone, Copy)]
+pub struct Format {
+    pub pretty: bool,
+    pub compress: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format { pretty: true, compress: false }
+    }
+}
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+pub fn set(format: Format) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let format = FORMAT.get().copied().unwrap_or_default();
+    let json = if format.pretty { serde_json::to_vec_pretty(value)? } else { serde_json::to_vec(value)? };
+    if !format.compress {
+        return Ok(json);
+    }
+    Ok(zstd::encode_all(json.as_slice(), LEVEL)?)
+}
+
+// Сжат файл или нет, решает содержимое, а не настройка: её могли поменять
+pub fn reader<'a>(mut input: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    let mut head = [0u8; 4];
+    let mut len = 0;
+    while len < head.len() {
+        match input.read(&mut head[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    let input = std::io::Cursor::new(head[..len].to_vec()).chain(input);
+    if head[..len] == ZSTD_MAGIC {
+        Ok(Box::new(zstd::Decoder::new(input)?))
+    } else {
+        Ok(Box::new(input))
+    }
+}
+
+pub fn read_to_string(input: impl Read) -> Result<String> {
+    let mut text = String::new();
+    reader(input)?.read_to_string(&mut text)?;
+    Ok(text)
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use crate::{Data, Task};
+
+// Тег -> (сессия -> число задач); регистр как у первого встреченного
+fn counts(data: &Data) -> BTreeMap<String, BTreeMap<String, usize>> {
+    let mut counts: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    let mut spelling: BTreeMap<String, String> = BTreeMap::new();
+    for (session, tasks) in &data.sessions {
+        for tag in tasks.iter().flat_map(|t| &t.tags) {
+            let name = spelling.entry(tag.to_lowercase()).or_insert_with(|| tag.clone()).clone();
+            *counts.entry(name).or_default().entry(session.clone()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+pub fn print_list(data: &Data) {
+    let counts = counts(data);
+    if counts.is_empty() {
+        println!("No tags yet, add one with 'a <task> +tag'");
+        return;
+    }
+    let mut rows: Vec<(&String, usize, String)> = counts
+    .iter()
+    .map(|(tag, sessions)| {
+        let per_session = sessions.iter().map(|(s, n)| format!("{} {}", s, n)).collect::<Vec<_>>().join(", ");
+        (tag, sessions.values().sum(), per_session)
+    })
+    .collect();
+    rows.sort_by_key(|(_, total, _)| std::cmp::Reverse(*total));
+    let width = rows.iter().map(|(tag, _, _)| tag.chars().count() + 1).max().unwrap_or(0);
+    for (tag, total, per_session) in rows {
+        println!("{:width$}  {:>3}  ({})", format!("+{}", tag), total, per_session);
+    }
+}
+
+// Для автодополнения в shell: известные теги с данным началом, по одному в строке
+pub fn print_completions(data: &Data, prefix: &str) {
+    let marker = if prefix.starts_with('#') { '#' } else { '+' };
+    let prefix = prefix.trim_start_matches(['#', '+']).to_lowercase();
+    for tag in counts(data).keys().filter(|t| t.to_lowercase().starts_with(&prefix)) {
+        println!("{}{}", marker, tag);
+    }
+}
+
+// "+home" или "#home" -> "home"
+pub fn parse(word: &str) -> Result<String> {
+    let tag = word.trim_start_matches(['#', '+']);
+    if !tag.starts_with(char::is_alphabetic) {
+        anyhow::bail!("'{}' is not a tag, start it with a letter, e.g. +urgent", word);
+    }
+    Ok(tag.to_string())
+}
+
+fn has(tags: &[String], tag: &str) -> bool {
+    tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+// Заменяет теги from на to, не создавая повторов; возвращает, изменилось ли что-то
+fn retag(tags: &mut Vec<String>, from: &[String], to: &str) -> bool {
+    if !tags.iter().any(|t| has(from, t)) {
+        return false;
+    }
+    let mut result: Vec<String> = Vec::new();
+    for tag in tags.drain(..) {
+        let tag = if has(from, &tag) { to.to_string() } else { tag };
+        if !has(&result, &tag) {
+            result.push(tag);
+        }
+    }
+    *tags = result;
+    true
+}
+
+fn retag_task(task: &mut Task, from: &[String], to: &str) -> usize {
+    let own = usize::from(retag(&mut task.tags, from, to));
+    own + task.subtasks.iter_mut().map(|sub| retag_task(sub, from, to)).sum::<usize>()
+}
+
+// Переименование во всех сессиях и сохранённых шаблонах разом: если хоть одна
+// задача с этим тегом заперта, не меняем ничего. Возвращает число задач
+pub fn rename(data: &mut Data, from: &[String], to: &str) -> Result<usize> {
+    let locked: Vec<&str> = data.sessions.values().flatten()
+    .filter(|t| t.locked && t.tags.iter().any(|tag| has(from, tag)))
+    .map(|t| t.description.as_str())
+    .collect();
+    if !locked.is_empty() {
+        anyhow::bail!("Locked tasks carry this tag, unlock them first: {}", locked.join(", "));
+    }
+    let count = data.sessions.values_mut().flatten().map(|task| retag_task(task, from, to)).sum();
+    for template in data.templates.values_mut() {
+        retag(&mut template.tags, from, to);
+    }
+    Ok(count)
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::Priority;
+
+// Заготовка задачи: в data-файле (template save) или в config.toml:
+// [templates.weekly-report]
+// description = "Weekly report"
+// tags = ["work"]
+// priority = "high"
+// due = "at fri 17h"
+// every = "1w"
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Template {
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<String>,
+}
+
+// Шаблоны из data-файла перекрывают одноимённые из конфига
+pub fn find<'a>(name: &str, saved: &'a BTreeMap<String, Template>, config: &'a BTreeMap<String, Template>) -> Result<&'a Template> {
+    if let Some(template) = saved.get(name).or_else(|| config.get(name)) {
+        return Ok(template);
+    }
+    let known: Vec<&String> = saved.keys().chain(config.keys()).collect();
+    if known.is_empty() {
+        anyhow::bail!("Template '{}' not found, create one with 'template save'", name);
+    }
+    anyhow::bail!(
+        "Template '{}' not found, known: {}",
+        name,
+        known.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+    )
+}
+
+impl Template {
+    // Дополняет аргументы add тем, что пользователь не указал сам:
+    // описание, время и правило повтора. Теги добавляются к указанным
+    pub fn expand(&self, parts: &[String]) -> Vec<String> {
+        let is_keyword = |p: &String| matches!(p.as_str(), "in" | "at" | "every" | "expires");
+        let mut parts = parts.to_vec();
+        if parts.first().is_none_or(|p| is_keyword(p) || crate::as_tag(p).is_some()) {
+            parts.insert(0, self.description.clone());
+        }
+        if let Some(due) = self.due.as_ref().filter(|_| !parts.iter().skip(1).any(|p| p == "in" || p == "at")) {
+            for (i, word) in due.split_whitespace().enumerate() {
+                parts.insert(1 + i, word.to_string());
+            }
+        }
+        if let Some(every) = self.every.as_ref().filter(|_| !parts.iter().any(|p| p == "every")) {
+            parts.push("every".to_string());
+            parts.extend(every.split_whitespace().map(String::from));
+        }
+        parts.extend(self.tags.iter().map(|t| format!("#{}", t)));
+        parts
+    }
+
+    pub fn summary(&self) -> String {
+        let mut line = self.description.clone();
+        if !self.tags.is_empty() {
+            line.push_str(&format!(" {}", crate::tags_label(&self.tags)));
+        }
+        if let Some(priority) = self.priority {
+            line.push_str(&format!(" [{}]", priority.label()));
+        }
+        if let Some(due) = &self.due {
+            line.push_str(&format!(", due {}", due));
+        }
+        if let Some(every) = &self.every {
+            line.push_str(&format!(", every {}", every));
+        }
+        line
+    }
+}
+
+pub fn print_list(saved: &BTreeMap<String, Template>, config: &BTreeMap<String, Template>) {
+    if saved.is_empty() && config.is_empty() {
+        println!("No templates, create one with 'template save <name> <task> [in|at <time>] [every <rule>]'");
+        return;
+    }
+    for (name, template) in saved {
+        println!("{:<16} {}", name, template.summary());
+    }
+    for (name, template) in config.iter().filter(|(name, _)| !saved.contains_key(*name)) {
+        println!("{:<16} {} (config)", name, template.summary());
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+use termcolor::{Color, ColorChoice};
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Default,
+    Deuteranopia,
+    HighContrast,
+    Mono,
+}
+
+// Цвета статусов, сроков и приоритетов
+pub struct Palette {
+    pub todo: Color,
+    pub in_progress: Color,
+    pub waiting: Color,
+    pub done: Color,
+    pub cancelled: Color,
+    pub overdue: Color,
+    pub upcoming: Color,
+    pub no_time: Color,
+    pub priority_low: Color,
+    pub priority_medium: Color,
+    pub priority_high: Color,
+    // Жирные яркие цвета везде, а не только в --big
+    pub strong: bool,
+}
+
+const DEFAULT: Palette = Palette {
+    todo: Color::Yellow,
+    in_progress: Color::Blue,
+    waiting: Color::Cyan,
+    done: Color::Green,
+    cancelled: Color::Ansi256(244),
+    overdue: Color::Red,
+    upcoming: Color::Yellow,
+    no_time: Color::Blue,
+    priority_low: Color::Ansi256(244),
+    priority_medium: Color::Yellow,
+    priority_high: Color::Red,
+    strong: false,
+};
+
+// Без пар красный/зелёный: синий, жёлтый и оранжевый из палитры Okabe-Ito
+const DEUTERANOPIA: Palette = Palette {
+    todo: Color::Ansi256(221),
+    in_progress: Color::Ansi256(117),
+    waiting: Color::Ansi256(175),
+    done: Color::Ansi256(32),
+    cancelled: Color::Ansi256(244),
+    overdue: Color::Ansi256(202),
+    upcoming: Color::Ansi256(221),
+    no_time: Color::Ansi256(117),
+    priority_low: Color::Ansi256(244),
+    priority_medium: Color::Ansi256(221),
+    priority_high: Color::Ansi256(202),
+    strong: false,
+};
+
+const HIGH_CONTRAST: Palette = Palette {
+    todo: Color::Yellow,
+    in_progress: Color::Cyan,
+    waiting: Color::Magenta,
+    done: Color::White,
+    cancelled: Color::White,
+    overdue: Color::Red,
+    upcoming: Color::Yellow,
+    no_time: Color::Cyan,
+    priority_low: Color::White,
+    priority_medium: Color::Yellow,
+    priority_high: Color::Red,
+    strong: true,
+};
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+pub fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+fn current() -> Theme {
+    THEME.get().copied().unwrap_or_default()
+}
+
+pub fn palette() -> &'static Palette {
+    match current() {
+        Theme::Default | Theme::Mono => &DEFAULT,
+        Theme::Deuteranopia => &DEUTERANOPIA,
+        Theme::HighContrast => &HIGH_CONTRAST,
+    }
+}
+
+// mono - без цветов вообще, остальное оформление (зачёркивание, ссылки) остаётся
+pub fn color_choice() -> ColorChoice {
+    if current() == Theme::Mono { ColorChoice::Never } else { ColorChoice::Always }
+}
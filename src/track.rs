@@ -0,0 +1,155 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{format_minutes, Data, Status};
+
+// Отрезок работы над задачей; end = None - таймер ещё идёт
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interval {
+    pub start: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl Interval {
+    fn duration(&self, now: DateTime<Utc>) -> TimeDelta {
+        self.end.unwrap_or(now) - self.start
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Group {
+    Task,
+    Session,
+    Day,
+}
+
+// Таймер один на все сессии: (сессия, индекс) задачи с незакрытым отрезком
+pub fn running(data: &Data) -> Option<(String, usize)> {
+    data.sessions.iter().find_map(|(name, tasks)| {
+        tasks.iter().position(|t| t.intervals.last().is_some_and(|i| i.end.is_none())).map(|idx| (name.clone(), idx))
+    })
+}
+
+// Закрывает идущий отрезок, возвращает описание задачи и сколько натикало
+pub fn stop(data: &mut Data, now: DateTime<Utc>) -> Option<(String, TimeDelta)> {
+    let (session, idx) = running(data)?;
+    let task = &mut data.sessions.get_mut(&session)?[idx];
+    let interval = task.intervals.last_mut()?;
+    interval.end = Some(now);
+    Some((task.description.clone(), interval.duration(now)))
+}
+
+pub fn elapsed(intervals: &[Interval], now: DateTime<Utc>) -> TimeDelta {
+    intervals.iter().map(|i| i.duration(now)).sum()
+}
+
+fn minutes(delta: TimeDelta) -> u32 {
+    u32::try_from(delta.num_minutes().max(0)).unwrap_or(u32::MAX)
+}
+
+// Суммы за последние days дней; отрезок, начатый раньше, считается с начала периода
+pub fn report(data: &Data, group: Group, days: u32, offset_hours: i64) {
+    let now = Utc::now();
+    let offset = TimeDelta::hours(offset_hours);
+    let since = now - TimeDelta::days(i64::from(days));
+    let mut totals: BTreeMap<String, TimeDelta> = BTreeMap::new();
+    let mut by_day: BTreeMap<NaiveDate, TimeDelta> = BTreeMap::new();
+
+    for (name, tasks) in &data.sessions {
+        for task in tasks {
+            for interval in &task.intervals {
+                let start = interval.start.max(since);
+                let end = interval.end.unwrap_or(now);
+                if end <= start {
+                    continue;
+                }
+                match group {
+                    Group::Task => *totals.entry(format!("{}: {}", name, task.description)).or_default() += end - start,
+                    Group::Session => *totals.entry(name.clone()).or_default() += end - start,
+                    // Отрезок через полночь делим между днями
+                    Group::Day => {
+                        let mut cursor = start;
+                        while cursor < end {
+                            let day = (cursor + offset).date_naive();
+                            let midnight = day.succ_opt().map_or(end, |d| d.and_hms_opt(0, 0, 0).unwrap().and_utc() - offset);
+                            let part_end = midnight.min(end);
+                            *by_day.entry(day).or_default() += part_end - cursor;
+                            cursor = part_end;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let rows: Vec<(String, TimeDelta)> = match group {
+        Group::Day => by_day.into_iter().map(|(day, total)| (day.format("%Y-%m-%d %a").to_string(), total)).collect(),
+        _ => {
+            let mut rows: Vec<(String, TimeDelta)> = totals.into_iter().collect();
+            rows.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+            rows
+        }
+    };
+
+    if rows.is_empty() {
+        println!("Nothing tracked in the last {} days", days);
+        return;
+    }
+    let width = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+    for (label, total) in &rows {
+        println!("{:width$}  {:>8}", label, format_minutes(minutes(*total)));
+    }
+    let total: TimeDelta = rows.iter().map(|(_, t)| *t).sum();
+    println!("{:width$}  {:>8}", "total", format_minutes(minutes(total)));
+}
+
+// Во сколько раз затраченное время больше оценки. Берём только сделанные задачи:
+// у незаконченной отработано не всё
+pub fn accuracy(data: &Data) {
+    let now = Utc::now();
+    // (задач, оценено минут, затрачено минут)
+    let mut sessions: BTreeMap<&str, (usize, u32, u32)> = BTreeMap::new();
+    let mut tags: BTreeMap<&str, (usize, u32, u32)> = BTreeMap::new();
+    for (name, tasks) in &data.sessions {
+        for task in tasks.iter().filter(|t| t.status == Status::Done) {
+            let Some(estimate) = task.estimate.filter(|&e| e > 0) else { continue };
+            let spent = minutes(elapsed(&task.intervals, now));
+            if spent == 0 {
+                continue;
+            }
+            let add = |sums: &mut (usize, u32, u32)| *sums = (sums.0 + 1, sums.1 + estimate, sums.2 + spent);
+            add(sessions.entry(name.as_str()).or_default());
+            for tag in &task.tags {
+                add(tags.entry(tag.as_str()).or_default());
+            }
+        }
+    }
+
+    if sessions.is_empty() {
+        println!("No finished tasks with both an estimate and tracked time");
+        return;
+    }
+
+    let ratio = |(_, estimate, spent): (usize, u32, u32)| f64::from(spent) / f64::from(estimate);
+    let width = sessions.keys().map(|name| name.chars().count()).max().unwrap_or(0).max("total".len());
+    println!("{:<width$}  {:>5}  {:>9}  {:>8}  {:>5}", "", "tasks", "estimated", "tracked", "ratio", width = width);
+    for (name, sums) in &sessions {
+        println!("{:<width$}  {:>5}  {:>9}  {:>8}  {:>4.1}x", name, sums.0, format_minutes(sums.1), format_minutes(sums.2), ratio(*sums), width = width);
+    }
+    let total = sessions.values().fold((0, 0, 0), |acc, sums| (acc.0 + sums.0, acc.1 + sums.1, acc.2 + sums.2));
+    if sessions.len() > 1 {
+        println!("{:<width$}  {:>5}  {:>9}  {:>8}  {:>4.1}x", "total", total.0, format_minutes(total.1), format_minutes(total.2), ratio(total), width = width);
+    }
+
+    // Хронически недооценённые: тег хотя бы на двух задачах и заметно дольше оценки
+    let mut under: Vec<(&str, (usize, u32, u32))> = tags.into_iter().filter(|(_, sums)| sums.0 >= 2 && ratio(*sums) >= UNDERESTIMATE_RATIO).collect();
+    under.sort_by(|a, b| ratio(b.1).total_cmp(&ratio(a.1)));
+    for (tag, sums) in under {
+        println!("Underestimated: #{} takes {:.1}x the estimate ({} tasks)", tag, ratio(sums), sums.0);
+    }
+}
+
+const UNDERESTIMATE_RATIO: f64 = 1.5;
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::{format_time, sort_tasks, Data};
+
+pub fn print_list(data: &Data, offset_hours: i64) {
+    if data.trash.is_empty() {
+        println!("Trash is empty");
+        return;
+    }
+    for (i, entry) in data.trash.iter().enumerate() {
+        let mut line = format!(
+            "  #{:<2} {} {}: {}",
+            i,
+            format_time(&Some(entry.deleted_at), offset_hours),
+            entry.session,
+            entry.task.description
+        );
+        if let Some(note) = &entry.note {
+            line.push_str(&format!(" ({})", note));
+        }
+        if let Some(id) = entry.task.short_id() {
+            line.push_str(&format!(" ={}", id));
+        }
+        println!("{}", line);
+    }
+}
+
+// Сначала ищем по id задачи, потом по номеру из trash list
+fn find(data: &Data, query: &str) -> Option<usize> {
+    let id = query.strip_prefix('=').unwrap_or(query).to_lowercase();
+    data.trash.iter().position(|e| e.task.short_id().is_some_and(|s| s == id)).or_else(|| {
+        query.strip_prefix('#').unwrap_or(query).parse().ok().filter(|&i| i < data.trash.len())
+    })
+}
+
+// Возвращает задачу в её сессию; истёкший срок жизни снимаем, иначе задача сразу уйдёт обратно
+pub fn restore(data: &mut Data, query: &str) -> Result<()> {
+    let Some(pos) = find(data, query) else {
+        anyhow::bail!("Nothing in trash matches '{}', see 'trash list'", query);
+    };
+    let mut entry = data.trash.remove(pos);
+    if entry.task.expires.is_some_and(|t| t <= Utc::now()) {
+        entry.task.expires = None;
+        println!("Cleared the expiry of '{}'", entry.task.description);
+    }
+    println!("Restored '{}' to session '{}'", entry.task.description, entry.session);
+    let tasks = data.sessions.entry(entry.session).or_default();
+    tasks.push(entry.task);
+    sort_tasks(tasks);
+    Ok(())
+}
+
+// Удаляет записи старше before (все, если None), возвращает сколько удалено
+pub fn empty(data: &mut Data, before: Option<DateTime<Utc>>) -> usize {
+    let count = data.trash.len();
+    data.trash.retain(|e| before.is_some_and(|b| e.deleted_at >= b));
+    count - data.trash.len()
+}
+
+pub fn count_before(data: &Data, before: Option<DateTime<Utc>>) -> usize {
+    data.trash.iter().filter(|e| before.is_none_or(|b| e.deleted_at < b)).count()
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use xdg::BaseDirectories;
+
+use crate::{delta, format_time, Data};
+
+// Сколько шагов назад помним
+const DEPTH: usize = 10;
+
+// Шаг хранит только то, что команда поменяла: прежние задачи изменённых сессий
+// (None - сессии не было) и прежние остальные поля, если менялись и они
+#[derive(Serialize, Deserialize)]
+struct Step {
+    command: String,
+    at: DateTime<Utc>,
+    #[serde(default)]
+    sessions: BTreeMap<String, Option<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    meta: Option<Value>,
+}
+
+// undo.json рядом с tasks.json: снимки до изменяющих команд и отменённые шаги для redo
+#[derive(Serialize, Deserialize, Default)]
+struct Journal {
+    #[serde(default)]
+    undo: Vec<Step>,
+    #[serde(default)]
+    redo: Vec<Step>,
+}
+
+fn journal_path() -> Result<PathBuf> {
+    let xdg = BaseDirectories::with_prefix("ttd")?;
+    Ok(xdg.place_config_file("undo.json")?)
+}
+
+fn load() -> Result<Journal> {
+    let path = journal_path()?;
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("{} is damaged, delete it to start over", path.display())),
+        Err(_) => Ok(Journal::default()),
+    }
+}
+
+fn save(journal: &Journal) -> Result<()> {
+    fs::write(journal_path()?, serde_json::to_string(journal)?)?;
+    Ok(())
+}
+
+pub fn snapshot(data: &Data) -> Result<Value> {
+    Ok(serde_json::to_value(data)?)
+}
+
+fn split(snapshot: &Value) -> (BTreeMap<String, Value>, Value) {
+    let mut meta = snapshot.clone();
+    let sessions = match meta.as_object_mut().and_then(|fields| fields.remove("sessions")) {
+        Some(Value::Object(sessions)) => sessions.into_iter().collect(),
+        _ => BTreeMap::new(),
+    };
+    (sessions, meta)
+}
+
+// Запоминает состояние до команды, если команда его изменила; новая правка обнуляет redo
+pub fn record(command: &str, before: Value, data: &Data) -> Result<()> {
+    let (old_sessions, old_meta) = split(&before);
+    let (new_sessions, new_meta) = split(&snapshot(data)?);
+    let mut sessions = BTreeMap::new();
+    for name in old_sessions.keys().chain(new_sessions.keys()) {
+        if old_sessions.get(name) != new_sessions.get(name) {
+            sessions.insert(name.clone(), old_sessions.get(name).cloned());
+        }
+    }
+    let meta = (old_meta != new_meta).then_some(old_meta);
+    if sessions.is_empty() && meta.is_none() {
+        return Ok(());
+    }
+    let mut journal = load()?;
+    journal.undo.push(Step { command: command.to_string(), at: Utc::now(), sessions, meta });
+    if journal.undo.len() > DEPTH {
+        journal.undo.remove(0);
+    }
+    journal.redo.clear();
+    save(&journal)
+}
+
+// undo и redo симметричны: берём шаг из одного стека, текущее состояние
+// тех же сессий и полей кладём в другой
+fn step(data: &mut Data, redo: bool) -> Result<()> {
+    let mut journal = load()?;
+    let (from, to) = if redo { (&mut journal.redo, &mut journal.undo) } else { (&mut journal.undo, &mut journal.redo) };
+    let Some(step) = from.pop() else {
+        println!("Nothing to {}", if redo { "redo" } else { "undo" });
+        return Ok(());
+    };
+    let mut current = BTreeMap::new();
+    for (name, tasks) in step.sessions {
+        current.insert(name.clone(), data.sessions.get(&name).map(serde_json::to_value).transpose()?);
+        match tasks {
+            Some(tasks) => {
+                data.sessions.insert(name, serde_json::from_value(tasks)?);
+            }
+            None => {
+                data.sessions.remove(&name);
+            }
+        }
+    }
+    let current_meta = match step.meta {
+        Some(meta) => {
+            let current_meta = delta::meta(data)?;
+            // Счётчик id назад не откатываем: иначе новые задачи получат id удалённых
+            let next_id = data.next_id;
+            delta::apply_meta(data, meta)?;
+            data.next_id = data.next_id.max(next_id);
+            Some(current_meta)
+        }
+        None => None,
+    };
+    to.push(Step { command: step.command.clone(), at: Utc::now(), sessions: current, meta: current_meta });
+    println!("{} '{}'", if redo { "Redid" } else { "Undid" }, step.command);
+    save(&journal)
+}
+
+pub fn undo(data: &mut Data) -> Result<()> {
+    step(data, false)
+}
+
+pub fn redo(data: &mut Data) -> Result<()> {
+    step(data, true)
+}
+
+pub fn print_list(offset_hours: i64) -> Result<()> {
+    let journal = load()?;
+    if journal.undo.is_empty() {
+        println!("Nothing to undo");
+    }
+    for (i, step) in journal.undo.iter().rev().enumerate() {
+        println!("{:>2}. {} {}", i + 1, format_time(&Some(step.at), offset_hours), step.command);
+    }
+    Ok(())
+}
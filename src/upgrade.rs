@@ -0,0 +1,117 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::{confirm, Data};
+
+// Изменения поведения, о которых стоит сказать после обновления: версия и одна строка
+const CHANGES: &[(&str, &str)] = &[
+    ("1.2.0", "a name close to several tasks now asks which one you meant; scripts can pass --no-interactive"),
+    ("1.2.0", "t takes several tasks: every word before in/at/from is a task name or index"),
+    ("1.2.0", "d, ud and r read 2-6 as a range of indexes unless a task has that name"),
+    ("1.2.0", "indexes mean what the last l showed; run l again to number new tasks"),
+    ("1.2.0", "cancel ends a repeating task for good, skip moves it to the next time"),
+    ("1.2.0", "timezone = \"UTC+3\" in [app] replaces timezone_offset_hours"),
+    ("1.2.0", "delta_save, pretty_json and compress in [app] control how tasks.json is written"),
+];
+
+// Устаревшее написание в config.toml: раздел, ключ и строка на замену по старому значению
+struct Deprecated {
+    section: &'static str,
+    key: &'static str,
+    rewrite: fn(&str) -> Option<String>,
+}
+
+const DEPRECATED: &[Deprecated] = &[
+    Deprecated { section: "app", key: "timezone_offset_hours", rewrite: timezone_line },
+];
+
+fn timezone_line(value: &str) -> Option<String> {
+    let hours: i64 = value.parse().ok()?;
+    Some(format!("timezone = \"UTC{:+}\"", hours))
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version.split('.').map(|part| part.parse().unwrap_or(0)).collect()
+}
+
+// Первый запуск новой версии: пишем, что поменялось, и предлагаем поправить конфиг.
+// Возвращает true, если версия в данных обновилась. quiet - next в приглашении shell
+// и --porcelain: там только одна строка, а заметки ждут следующей обычной команды
+pub fn check(data: &mut Data, config_path: &Path, quiet: bool) -> Result<bool> {
+    let current = env!("CARGO_PKG_VERSION");
+    let previous = data.version.clone();
+    // Данные от более новой версии не трогаем, версию не понижаем
+    if previous.as_deref().is_some_and(|p| parse_version(p) >= parse_version(current)) {
+        return Ok(false);
+    }
+    // Новая установка: рассказывать не о чем
+    if previous.is_none() && data.sessions.is_empty() {
+        data.version = Some(current.to_string());
+        return Ok(true);
+    }
+    if quiet {
+        eprintln!("ttd was upgraded to {}, run 'ttd' to see what changed", current);
+        return Ok(false);
+    }
+    data.version = Some(current.to_string());
+
+    let since = previous.as_deref().map(parse_version);
+    let notes: Vec<&str> = CHANGES.iter()
+    .filter(|(version, _)| since.as_ref().is_none_or(|since| parse_version(version) > *since))
+    .map(|(_, note)| *note)
+    .collect();
+    eprintln!("ttd was upgraded from {} to {}", previous.as_deref().unwrap_or("an earlier version"), current);
+    for note in notes {
+        eprintln!("  - {}", note);
+    }
+    migrate(config_path)?;
+    Ok(true)
+}
+
+fn migrate(config_path: &Path) -> Result<()> {
+    let Ok(text) = fs::read_to_string(config_path) else {
+        return Ok(());
+    };
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut found = Vec::new();
+    let mut section = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once('=') else {
+            continue;
+        };
+        let value = value.split('#').next().unwrap_or("").trim();
+        let new = DEPRECATED.iter()
+        .filter(|d| d.section == section && d.key == key.trim())
+        .find_map(|d| (d.rewrite)(value));
+        if let Some(new) = new {
+            found.push((i, new));
+        }
+    }
+    if found.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("Deprecated settings in {}:", config_path.display());
+    for (i, new) in &found {
+        eprintln!("  line {}: {} -> {}", i + 1, lines[*i].trim(), new);
+    }
+    if !confirm::is_interactive() || !confirm::ask("Update config.toml? The old file is kept as config.toml.bak")? {
+        eprintln!("The old spelling still works for now; update it by hand when convenient");
+        return Ok(());
+    }
+    fs::write(config_path.with_extension("toml.bak"), &text)?;
+    // Комментарий в конце строки сохраняем
+    for (i, new) in found {
+        let comment = lines[i].find('#').map(|pos| format!("  {}", &lines[i][pos..])).unwrap_or_default();
+        lines[i] = format!("{}{}", new, comment);
+    }
+    fs::write(config_path, lines.join("\n") + "\n")?;
+    println!("Updated {}", config_path.display());
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+
+use crate::Data;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Vacation {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Vacation {
+    // "2025-08-01..2025-08-14", обе даты включительно
+    pub fn parse(range: &str) -> Result<Vacation> {
+        let (start, end) = range.split_once("..")
+        .ok_or_else(|| anyhow::anyhow!("Expected a range like 2025-08-01..2025-08-14"))?;
+        let start = NaiveDate::parse_from_str(start.trim(), "%Y-%m-%d")?;
+        let end = NaiveDate::parse_from_str(end.trim(), "%Y-%m-%d")?;
+        if end < start {
+            anyhow::bail!("Vacation ends before it starts");
+        }
+        Ok(Vacation { start, end })
+    }
+
+    pub fn days(&self) -> i64 {
+        (self.end - self.start).num_days() + 1
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>, offset_hours: i64) -> bool {
+        let local = (time + TimeDelta::hours(offset_hours)).date_naive();
+        self.start <= local && local <= self.end
+    }
+
+    pub fn is_over(&self, offset_hours: i64) -> bool {
+        (Utc::now() + TimeDelta::hours(offset_hours)).date_naive() > self.end
+    }
+}
+
+// После возвращения предлагаем сдвинуть задачи, которые пришлись на отпуск.
+// Возвращает true, если данные изменились
+pub fn check_return(data: &mut Data, offset_hours: i64) -> Result<bool> {
+    let Some(vacation) = data.vacation else {
+        return Ok(false);
+    };
+    if !vacation.is_over(offset_hours) || !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    let missed = data.sessions.values()
+    .flatten()
+    .filter(|t| t.is_open() && t.time.is_some_and(|time| vacation.contains(time, offset_hours)))
+    .count();
+
+    if missed > 0 {
+        println!(
+            "Welcome back! {} tasks were due during your vacation ({} .. {}).",
+            missed, vacation.start, vacation.end
+        );
+        if crate::confirm::ask(&format!("Shift them forward by {} days?", vacation.days()))? {
+            let shift = TimeDelta::days(vacation.days());
+            for task in data.sessions.values_mut().flatten() {
+                if let Some(time) = task.time.filter(|&t| task.is_open() && !task.locked && vacation.contains(t, offset_hours)) {
+                    task.time = Some(time + shift);
+                }
+            }
+            for tasks in data.sessions.values_mut() {
+                crate::sort_tasks(tasks);
+            }
+            println!("Shifted {} tasks", missed);
+        }
+    }
+
+    data.vacation = None;
+    Ok(true)
+}